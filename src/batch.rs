@@ -0,0 +1,121 @@
+//! Configuration for converting several directories of legacy tests in one run, each with its
+//! own output directory, format, and options.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::{ConversionError, ConvertOptions};
+
+/// The directories a batch conversion converts, read from a YAML or JSON file like:
+///
+/// ```yaml
+/// options:
+///   schemaVersion: "1.10"
+/// directories:
+///   - input: server-discovery-and-monitoring/tests/integration
+///     output: server-discovery-and-monitoring/tests/unified
+///     options:
+///       defaultDatabaseName: sdam-tests
+///   - input: transactions/tests/legacy
+///     output: transactions/tests/unified
+///     format: json
+///     options:
+///       defaultDatabaseName: transaction-tests
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BatchConfig {
+    /// Options for every directory, as camelCase option names. Each directory's own options
+    /// take precedence over these.
+    #[serde(default)]
+    pub options: Mapping,
+
+    pub directories: Vec<DirectoryConfig>,
+}
+
+/// A directory of legacy tests to convert, and how.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DirectoryConfig {
+    /// The directory of legacy tests. Relative paths are relative to the config file.
+    pub input: PathBuf,
+
+    /// The directory to write the converted files to. Relative paths are relative to the config
+    /// file.
+    pub output: PathBuf,
+
+    #[serde(default)]
+    pub format: OutputFormat,
+
+    /// Options for this directory, as camelCase option names.
+    #[serde(default)]
+    pub options: Mapping,
+}
+
+/// The format to write converted files in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Yaml,
+
+    /// JSON, with the YAML anchors resolved, like the JSON files the specifications repository
+    /// keeps alongside its YAML ones.
+    Json,
+}
+
+impl OutputFormat {
+    /// The extension of files written in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Yaml => "yml",
+            Self::Json => "json",
+        }
+    }
+
+    /// Renders a converted file, given as YAML, in this format.
+    pub fn render(self, yaml: String) -> Result<String, ConversionError> {
+        match self {
+            Self::Yaml => Ok(yaml),
+            Self::Json => {
+                let file: Value =
+                    serde_yaml::from_str(&yaml).map_err(ConversionError::Malformed)?;
+                let json = serde_json::to_string_pretty(&file)
+                    .map_err(|e| ConversionError::Io(e.into()))?;
+                Ok(json + "\n")
+            }
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Parses a config file's YAML or JSON contents, resolving the directories in it relative to
+    /// `base`, the directory containing the file.
+    pub fn from_str(
+        config: &str,
+        base: &Path,
+    ) -> Result<Self, serde_path_to_error::Error<serde_yaml::Error>> {
+        let mut config: Self =
+            serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(config))?;
+        for directory in config.directories.iter_mut() {
+            directory.input = base.join(&directory.input);
+            directory.output = base.join(&directory.output);
+        }
+        Ok(config)
+    }
+
+    /// The options to convert a directory with: the batch's options overridden by the
+    /// directory's, with any that neither sets taking their default values.
+    pub fn options(
+        &self,
+        directory: &DirectoryConfig,
+    ) -> Result<ConvertOptions, serde_path_to_error::Error<serde_yaml::Error>> {
+        let mut options = self.options.clone();
+        for (name, value) in directory.options.iter() {
+            options.insert(name.clone(), value.clone());
+        }
+        serde_path_to_error::deserialize(Value::Mapping(options))
+    }
+}
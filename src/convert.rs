@@ -0,0 +1,673 @@
+//! Conversion of whole legacy test files.
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use bson::{doc, Document};
+use clap::Parser;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    crud_v2::{self, RunOn, TestData, KEY_VAULT_NAMESPACE},
+    diff,
+    extended_json,
+    operations::OperationRegistry,
+    report::{self, Capabilities, ConversionReport, WarningKind, Warnings, CSOT_SCHEMA_VERSION},
+    scalars,
+    unified::{
+        self,
+        ClientEntity,
+        CreateEntity,
+        DatabaseEntity,
+        InitialData,
+        Namespace,
+        RunOnRequirements,
+        Test,
+    },
+    entities::{Entity, EntityNaming, EntityRegistry},
+    unknown_operation_placeholder,
+    ConversionError,
+    SCHEMA_VERSION,
+};
+
+/// Matches the names of operations the converter didn't recognize, which it serializes as
+/// placeholders so they can be flagged in the output.
+static UNKNOWN_OPERATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^(\s*)(- )?name: xUNKNOWN_OPERATION_PLACEHOLDER_(\w+)$").unwrap()
+});
+
+/// The placeholder serialized as the outcome of tests whose outcome is an alias of the initial
+/// data.
+static INITIAL_DATA_ALIAS: &str = "xINITIAL_DATA_PLACEHOLDER";
+
+/// The most tests that `scope_setup_client` creates the setup client in, rather than at the file
+/// level.
+const MAX_SCOPED_SETUP_CLIENTS: usize = 2;
+
+/// Options that control how legacy test files are converted. They can also be deserialized from
+/// a map of camelCase option names, with any that are missing taking their default values.
+#[derive(Clone, Debug, Deserialize, Parser)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ConvertOptions {
+    /// The unified test format schema version that converted files declare.
+    #[clap(long, default_value = SCHEMA_VERSION)]
+    pub schema_version: String,
+
+    /// The database name to use for files that don't specify a `database_name`.
+    #[clap(long, default_value = "sdam-tests")]
+    pub default_database_name: String,
+
+    /// Populate `ignoreCommandMonitoringEvents` on generated clients that observe command events.
+    #[clap(long)]
+    pub ignore_command_monitoring_events: bool,
+
+    /// The commands to ignore when `--ignore-command-monitoring-events` is specified.
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        default_values = &["isMaster", "hello", "configureFailPoint"]
+    )]
+    pub ignored_commands: Vec<String>,
+
+    /// Precede operations the converter doesn't recognize with a `# TODO` comment in the output.
+    #[clap(long)]
+    pub todo_comments: bool,
+
+    /// Create entities that are identical across all of a file's tests once in the file-level
+    /// `createEntities` rather than in each test.
+    #[clap(long)]
+    pub hoist_entities: bool,
+
+    /// Anchor `initialData` and write the outcome of tests that leave it unchanged as an alias
+    /// of it, like some hand-written unified tests do, rather than repeating the documents.
+    #[clap(long)]
+    pub alias_initial_data: bool,
+
+    /// Create the client that configures fail points and runs admin commands, and the admin
+    /// database, in the tests that use them rather than at the file level, when no more than two
+    /// tests do.
+    #[clap(long)]
+    pub scope_setup_client: bool,
+
+    /// Rewrite legacy topology names (e.g. `sharded-replicaset`) in `runOnRequirements` to their
+    /// modern equivalents.
+    #[clap(long)]
+    pub normalize_topologies: bool,
+
+    /// Additional events (e.g. `serverHeartbeatStartedEvent`) that every test's client should
+    /// observe, beyond those inferred from the test's expectations and operations.
+    #[clap(long, use_value_delimiter = true)]
+    pub observe_events: Vec<String>,
+
+    /// How to name the entities that converted files create.
+    #[clap(long, value_enum, default_value_t)]
+    pub entity_naming: EntityNaming,
+
+    /// Fail on operations the converter doesn't recognize instead of passing them through
+    /// unchanged.
+    #[clap(long)]
+    pub strict: bool,
+
+    /// The description of the converted file, used by `convert_str`. The binary describes each
+    /// file by its name instead.
+    #[clap(skip)]
+    pub description: Option<String>,
+
+    /// The converters to use for legacy operations. Library users can register their own on
+    /// top of the default ones.
+    #[clap(skip)]
+    #[serde(skip)]
+    pub operations: OperationRegistry,
+}
+
+impl Default for ConvertOptions {
+    /// The same options as running the converter with no command line flags.
+    fn default() -> Self {
+        Self::parse_from([env!("CARGO_PKG_NAME")])
+    }
+}
+
+impl ConvertOptions {
+    /// A builder starting from the default options.
+    pub fn builder() -> ConvertOptionsBuilder {
+        ConvertOptionsBuilder {
+            options: Self::default(),
+        }
+    }
+}
+
+/// Builds [`ConvertOptions`] for library callers. Each method corresponds to the command line
+/// flag of the same name.
+#[derive(Debug)]
+pub struct ConvertOptionsBuilder {
+    options: ConvertOptions,
+}
+
+impl ConvertOptionsBuilder {
+    pub fn schema_version(mut self, schema_version: impl Into<String>) -> Self {
+        self.options.schema_version = schema_version.into();
+        self
+    }
+
+    pub fn default_database_name(mut self, database_name: impl Into<String>) -> Self {
+        self.options.default_database_name = database_name.into();
+        self
+    }
+
+    pub fn entity_naming(mut self, naming: EntityNaming) -> Self {
+        self.options.entity_naming = naming;
+        self
+    }
+
+    pub fn hoist_entities(mut self, hoist_entities: bool) -> Self {
+        self.options.hoist_entities = hoist_entities;
+        self
+    }
+
+    pub fn alias_initial_data(mut self, alias_initial_data: bool) -> Self {
+        self.options.alias_initial_data = alias_initial_data;
+        self
+    }
+
+    pub fn scope_setup_client(mut self, scope_setup_client: bool) -> Self {
+        self.options.scope_setup_client = scope_setup_client;
+        self
+    }
+
+    /// Populates `ignoreCommandMonitoringEvents` with the given commands on generated clients
+    /// that observe command events.
+    pub fn ignored_commands<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.ignore_command_monitoring_events = true;
+        self.options.ignored_commands = commands.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn observe_events<I, S>(mut self, events: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.observe_events = events.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn normalize_topologies(mut self, normalize_topologies: bool) -> Self {
+        self.options.normalize_topologies = normalize_topologies;
+        self
+    }
+
+    pub fn todo_comments(mut self, todo_comments: bool) -> Self {
+        self.options.todo_comments = todo_comments;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.options.description = Some(description.into());
+        self
+    }
+
+    pub fn operations(mut self, operations: OperationRegistry) -> Self {
+        self.options.operations = operations;
+        self
+    }
+
+    pub fn build(self) -> ConvertOptions {
+        self.options
+    }
+}
+
+/// Converts the YAML contents of a legacy test file into a unified test file.
+pub fn convert_str(
+    input: &str,
+    options: &ConvertOptions,
+) -> Result<(String, ConversionReport), ConversionError> {
+    let description = options
+        .description
+        .as_deref()
+        .unwrap_or("converted legacy tests");
+
+    // the converter's placeholders are replaced textually, so any already in the input would be
+    // mangled.
+    if let Some(placeholder) = input
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .find(|word| {
+            word.ends_with("_PLACEHOLDER")
+                || word.contains("_PLACEHOLDER_")
+                || word.starts_with("xENTITY_")
+                || word.starts_with("xQUOTED_STRING_")
+        })
+    {
+        return Err(
+            ConversionError::PlaceholderCollision(placeholder.to_string()).in_file(description),
+        );
+    }
+
+    let old: crud_v2::TestFile = {
+        let _span = tracing::debug_span!("parse", file = %description).entered();
+        extended_json::from_str(input).map_err(|e| ConversionError::from(e).in_file(description))?
+    };
+    convert(description, old, options)
+}
+
+/// Reads a legacy test file from `reader` and writes the converted unified test file to
+/// `writer`. Nothing is written if the conversion fails.
+pub fn convert_reader<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    options: &ConvertOptions,
+) -> Result<ConversionReport, ConversionError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let (output, report) = convert_str(&input, options)?;
+    writer.write_all(output.as_bytes())?;
+    writer.flush()?;
+    Ok(report)
+}
+
+/// Converts a parsed legacy test file into a unified test file, using its name as the
+/// description. The report describes anything that may need checking in the converted file.
+#[tracing::instrument(level = "debug", skip_all, fields(file = %file_name.as_ref()))]
+pub fn convert(
+    file_name: impl AsRef<str>,
+    old: crud_v2::TestFile,
+    options: &ConvertOptions,
+) -> Result<(String, ConversionReport), ConversionError> {
+    let warnings = Warnings::default();
+    let mut ents = Vec::new();
+    let mut tests = Vec::new();
+    // the tests that use the setup client, to configure fail points or run admin commands, and
+    // whether each runs admin commands.
+    let setup_client_tests: Vec<(usize, bool)> = old
+        .tests
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| {
+            let admin_command = t.operations.iter().any(|op| op.runs("runAdminCommand"));
+            let fail_point = t.fail_point.is_some()
+                || t.operations.iter().any(|op| op.runs("configureFailPoint"));
+            (admin_command || fail_point).then_some((i, admin_command))
+        })
+        .collect();
+    let contains_admin_command = setup_client_tests.iter().any(|(_, admin)| *admin);
+
+    // the collections that aggregations write to, and that change streams tests' operations
+    // run in, need to be in the initial data too, so that the runner drops them before each
+    // test.
+    // legacy change streams runners also dropped the second namespace before each test, whether
+    // or not the test used it.
+    let mut other_collections = Vec::new();
+    if old.database2_name.is_some() || old.collection2_name.is_some() {
+        other_collections.push((
+            old.database2_name.clone(),
+            old.collection2_name
+                .clone()
+                .unwrap_or_else(|| old.collection_name.clone()),
+        ));
+    }
+    for collection in old
+        .tests
+        .iter()
+        .flat_map(|test| test.operations.iter())
+        .filter_map(|op| {
+            op.aggregation_output().or_else(|| {
+                let collection = op.collection.as_ref().unwrap_or(&old.collection_name);
+                (op.database.is_some() || op.collection.is_some())
+                    .then(|| (op.database.clone(), collection.clone()))
+            })
+        })
+    {
+        if !other_collections.contains(&collection) {
+            other_collections.push(collection);
+        }
+    }
+
+    let database_name = old
+        .database_name
+        .as_deref()
+        .unwrap_or(options.default_database_name.as_str());
+    let namespace = Namespace {
+        database_name,
+        collection_name: old.collection_name.as_str(),
+        bucket_name: old.bucket_name.as_deref(),
+    };
+
+    for problem in old.run_on.iter().flatten().flat_map(RunOn::server_version_problems) {
+        warnings.warn(WarningKind::ServerVersion(problem));
+    }
+    for old_test in old.tests.iter() {
+        let start = warnings.len();
+        for problem in old_test.server_version_problems() {
+            warnings.warn(WarningKind::ServerVersion(problem));
+        }
+        warnings.locate(start, |location| {
+            location.test = Some(old_test.description.clone());
+        });
+    }
+
+    let mut test_descriptions = Vec::new();
+    for old_test in old.tests {
+        let _span = tracing::debug_span!("test", description = %old_test.description).entered();
+        let legacy_description = old_test.description.clone();
+        let test = Test::from_crud_v2(old_test, namespace, options, &warnings)
+            .map_err(|e| e.in_file(file_name.as_ref()))?;
+        test_descriptions.push((legacy_description, test.description.clone()));
+        tests.push(test);
+    }
+
+    let hoisted_entities = if options.hoist_entities {
+        unified::hoist_entities(&mut tests)
+    } else {
+        None
+    };
+
+    // the database and collection names are anchored wherever they first appear, which is in the
+    // hoisted entities if there are any.
+    let mut entities = EntityRegistry::default();
+    let (database_name_placeholder, collection_name_placeholder) = match hoisted_entities {
+        Some(_) => (
+            entities.refer(Entity::DatabaseName),
+            entities.refer(Entity::CollectionName),
+        ),
+        None => (
+            entities.define(Entity::DatabaseName),
+            entities.define(Entity::CollectionName),
+        ),
+    };
+    entities.define_value(Entity::DatabaseName, database_name);
+    entities.define_value(Entity::CollectionName, old.collection_name.as_str());
+
+    let mut create_options = old.create_options;
+    if let Some(schema) = old.json_schema {
+        create_options
+            .get_or_insert_with(Document::new)
+            .insert("validator", doc! { "$jsonSchema": schema });
+    }
+    if let Some(encrypted_fields) = old.encrypted_fields {
+        create_options
+            .get_or_insert_with(Document::new)
+            .insert("encryptedFields", encrypted_fields);
+    }
+
+    // the unified format seeds the key vault like any other collection, before the test
+    // collection is created with a schema that refers to the keys.
+    let mut initial_data: Vec<_> = old
+        .key_vault_data
+        .map(|keys| InitialData {
+            database_name: KEY_VAULT_NAMESPACE.0.to_string(),
+            collection_name: KEY_VAULT_NAMESPACE.1.to_string(),
+            create_options: None,
+            documents: keys,
+        })
+        .into_iter()
+        .collect();
+    match old.data {
+        TestData::Single(docs) => initial_data.push(InitialData {
+            collection_name: collection_name_placeholder.to_string(),
+            database_name: database_name_placeholder.to_string(),
+            create_options,
+            documents: docs,
+        }),
+        TestData::Many(_) => {
+            return Err(ConversionError::UnsupportedData(
+                "data for multiple collections".to_string(),
+            )
+            .in_file(file_name.as_ref()))
+        }
+    }
+    for (output_database, output_collection) in other_collections {
+        let in_test_database = output_database
+            .as_deref()
+            .is_none_or(|output_database| output_database == database_name);
+        if in_test_database && output_collection == old.collection_name {
+            continue;
+        }
+        initial_data.push(InitialData {
+            collection_name: output_collection,
+            database_name: match output_database {
+                Some(output_database) if !in_test_database => output_database,
+                _ => entities.refer(Entity::DatabaseName),
+            },
+            create_options: None,
+            documents: Vec::new(),
+        });
+    }
+
+    if options.alias_initial_data {
+        for test in tests.iter_mut() {
+            let unchanged = test.outcome.as_ref().is_some_and(|outcome| {
+                outcome.len() == initial_data.len()
+                    && outcome.iter().zip(initial_data.iter()).all(|(a, b)| a.same_as(b))
+            });
+            if unchanged {
+                test.alias_outcome();
+            }
+        }
+    }
+    if options.scope_setup_client && setup_client_tests.len() <= MAX_SCOPED_SETUP_CLIENTS {
+        for (i, admin_command) in setup_client_tests {
+            let created = setup_entities(admin_command)
+                .iter()
+                .map(bson::to_bson)
+                .collect::<Result<_, _>>()?;
+            tests[i].create_first(created);
+        }
+    } else if !setup_client_tests.is_empty() {
+        ents.extend(setup_entities(contains_admin_command));
+    }
+    for test in tests.iter() {
+        entities.merge(&test.entities);
+    }
+
+    let mut test_file = unified::TestFile {
+        description: file_name.as_ref().to_string(),
+        schema_version: options.schema_version.clone(),
+        run_on_requirements: old.run_on.map(|run_on| {
+            RunOnRequirements::from_crud_v2(run_on, options.normalize_topologies, &warnings)
+        }),
+        create_entities: Some(
+            ents.iter()
+                .map(bson::to_bson)
+                .chain(hoisted_entities.into_iter().flatten().map(Ok))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+        .filter(|entities| !entities.is_empty()),
+        initial_data: Some(initial_data),
+        tests,
+    };
+
+    for entity in test_file.create_entities.iter().flatten() {
+        entities.record(entity);
+    }
+
+    // runners that don't support CSOT would ignore the timeouts rather than fail, so files using
+    // them declare the schema version that added them instead of just warning.
+    let file_bson = bson::to_bson(&test_file)?;
+    if report::uses_csot(&file_bson)
+        && report::older_than(&test_file.schema_version, CSOT_SCHEMA_VERSION)
+    {
+        test_file.schema_version = CSOT_SCHEMA_VERSION.to_string();
+    }
+    // likewise, files whose topologies were normalized declare the schema version that added the
+    // topologies they were normalized to.
+    if options.normalize_topologies {
+        let requirements: Vec<_> = test_file
+            .run_on_requirements
+            .iter()
+            .chain(test_file.tests.iter().filter_map(|t| t.run_on_requirements.as_ref()))
+            .flatten()
+            .collect();
+        let version = report::required_schema_version(&bson::to_bson(&requirements)?);
+        if report::older_than(&test_file.schema_version, &version) {
+            test_file.schema_version = version;
+        }
+    }
+    let capabilities = Capabilities::of(&file_bson);
+    let required_schema_version = capabilities.schema_version.clone();
+
+    // the emitter leaves some strings that YAML 1.1 parsers read as numbers or timestamps
+    // unquoted, so those are serialized as placeholders and quoted along with the entities.
+    let serialize_span = tracing::debug_span!("serialize").entered();
+    let file_value = extended_json::to_value(&test_file)?;
+    let mut value = file_value.clone();
+    let aliases_initial_data = test_file.tests.iter().any(|test| test.outcome_is_initial_data);
+    if aliases_initial_data {
+        let test_values = value
+            .get_mut("tests")
+            .and_then(serde_yaml::Value::as_sequence_mut)
+            .into_iter()
+            .flatten();
+        for (test, test_value) in test_file.tests.iter().zip(test_values) {
+            if !test.outcome_is_initial_data {
+                continue;
+            }
+            if let Some(test_value) = test_value.as_mapping_mut() {
+                test_value.insert("outcome".into(), INITIAL_DATA_ALIAS.into());
+            }
+        }
+    }
+    let quoted_strings = scalars::quote_ambiguous(&mut value);
+    let raw_string = serde_yaml::to_string(&value).map_err(ConversionError::Emit)?;
+    serialize_span.exit();
+    entities
+        .check_anchors(&raw_string, options.entity_naming)
+        .map_err(|e| e.in_file(file_name.as_ref()))?;
+    let placeholders_span = tracing::debug_span!("replace_placeholders").entered();
+    let mut replacements: Vec<_> = quoted_strings
+        .iter()
+        .map(|(placeholder, quoted)| (placeholder.as_str(), quoted.as_str()))
+        .collect();
+    if aliases_initial_data {
+        // the initial data is a top-level key, the only one at the start of a line.
+        replacements.push(("\ninitialData:\n", "\ninitialData: &initialData\n"));
+        replacements.push((INITIAL_DATA_ALIAS, "*initialData"));
+    }
+    let mut raw_string = entities.emit_anchors(&raw_string, options.entity_naming, &replacements);
+
+    // the regex needs another pass over the file, which most files don't need.
+    if raw_string.contains("xUNKNOWN_OPERATION_PLACEHOLDER_") {
+        let replacement = if options.todo_comments {
+            "${1}# TODO: unrecognized operation, verify its conversion\n${1}${2}name: ${3}"
+        } else {
+            "${1}${2}name: ${3}"
+        };
+        raw_string = UNKNOWN_OPERATION
+            .replace_all(&raw_string, replacement)
+            .into_owned();
+    }
+    placeholders_span.exit();
+
+    let resolved_values = entities.resolved_values(options.entity_naming);
+    tracing::debug_span!("verify_round_trip")
+        .in_scope(|| verify_round_trip(&raw_string, file_value, &resolved_values))
+        .map_err(|e| e.in_file(file_name.as_ref()))?;
+
+    #[cfg(feature = "validate")]
+    tracing::debug_span!("validate")
+        .in_scope(|| crate::validate::validate(&raw_string))
+        .map_err(|e| e.in_file(file_name.as_ref()))?;
+    #[cfg(feature = "json-schema")]
+    tracing::debug_span!("validate_schema")
+        .in_scope(|| crate::schema::validate(&raw_string))
+        .map_err(|e| e.in_file(file_name.as_ref()))?;
+
+    if report::older_than(&test_file.schema_version, &required_schema_version) {
+        warnings.warn(WarningKind::SchemaVersion {
+            declared: test_file.schema_version.clone(),
+            required: required_schema_version.clone(),
+        });
+    }
+    warnings.locate(0, |location| {
+        location.file = Some(file_name.as_ref().to_string());
+    });
+    let warnings = warnings.into_inner();
+
+    let mut substitutions = entities.substitutions(options.entity_naming);
+    for warning in warnings.iter() {
+        if let WarningKind::UnknownOperation(ref name) = warning.kind {
+            substitutions.insert(unknown_operation_placeholder(name), name.clone());
+        }
+    }
+
+    let report = ConversionReport {
+        warnings,
+        substitutions,
+        schema_version: test_file.schema_version,
+        required_schema_version,
+        tests: test_descriptions,
+        capabilities,
+    };
+    Ok((raw_string, report))
+}
+
+/// The setup client that configures fail points, along with the admin database on it if admin
+/// commands are run.
+fn setup_entities(admin_command: bool) -> Vec<CreateEntity> {
+    let mut ents = vec![CreateEntity::Client(ClientEntity {
+        id: Entity::SetupClient.definition(),
+        observe_events: None,
+        ignore_command_monitoring_events: None,
+        observe_sensitive_commands: None,
+        uri_options: None,
+        use_multiple_mongoses: None,
+        auto_encrypt_opts: None,
+    })];
+    if admin_command {
+        ents.push(CreateEntity::Database(DatabaseEntity {
+            id: Entity::AdminDatabase.definition(),
+            client: Entity::SetupClient.reference(),
+            database_name: "admin".to_string(),
+            database_options: None,
+        }))
+    }
+    ents
+}
+
+/// Checks that a converted file reads back as the value it was serialized from, with its
+/// placeholders resolved to what their anchors and aliases stand for. The placeholders and
+/// unrecognized operations are replaced in the serialized text, which can break its structure
+/// without the YAML becoming invalid.
+fn verify_round_trip(
+    yaml: &str,
+    mut expected: serde_yaml::Value,
+    resolved_values: &BTreeMap<String, String>,
+) -> Result<(), ConversionError> {
+    let actual: serde_yaml::Value =
+        serde_yaml::from_str(yaml).map_err(ConversionError::Malformed)?;
+    resolve_placeholders(&mut expected, resolved_values);
+    match diff::diff(&expected, &actual).into_iter().next() {
+        Some(difference) => Err(ConversionError::RoundTrip(Box::new(difference))),
+        None => Ok(()),
+    }
+}
+
+fn resolve_placeholders(value: &mut serde_yaml::Value, resolved_values: &BTreeMap<String, String>) {
+    match value {
+        serde_yaml::Value::String(s) => {
+            if let Some(resolved) = resolved_values.get(s.as_str()) {
+                *s = resolved.clone();
+            } else if let Some(name) = s.strip_prefix("xUNKNOWN_OPERATION_PLACEHOLDER_") {
+                *s = name.to_string();
+            }
+        }
+        serde_yaml::Value::Mapping(mapping) => mapping
+            .iter_mut()
+            .for_each(|(_, value)| resolve_placeholders(value, resolved_values)),
+        serde_yaml::Value::Sequence(values) => values
+            .iter_mut()
+            .for_each(|value| resolve_placeholders(value, resolved_values)),
+        _ => {}
+    }
+}
@@ -0,0 +1,635 @@
+//! The legacy v2 test format.
+
+use bson::{Bson, Document};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Commands whose events are redacted by drivers unless a client explicitly opts into
+/// observing them.
+static SENSITIVE_COMMANDS: &[&str] = &[
+    "authenticate",
+    "saslStart",
+    "saslContinue",
+    "getnonce",
+    "createUser",
+    "updateUser",
+    "copydbgetnonce",
+    "copydbsaslstart",
+    "copydb",
+];
+
+/// The database and collection that legacy runners inserted a file's `key_vault_data` into.
+pub(crate) static KEY_VAULT_NAMESPACE: (&str, &str) = ("keyvault", "datakeys");
+
+#[serde_with::skip_serializing_none]
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestFile {
+    #[serde(rename = "runOn")]
+    pub run_on: Option<Vec<RunOn>>,
+    pub database_name: Option<String>,
+    pub collection_name: String,
+
+    /// The other database and collection that change streams tests' operations run in, which
+    /// legacy runners dropped before each test like the test collection.
+    pub database2_name: Option<String>,
+    pub collection2_name: Option<String>,
+    pub bucket_name: Option<String>,
+    pub create_options: Option<Document>,
+
+    /// The JSON schema client-side encryption tests create the test collection with.
+    pub json_schema: Option<Document>,
+
+    /// The encrypted fields queryable encryption tests create the test collection with.
+    pub encrypted_fields: Option<Document>,
+
+    /// The data keys client-side encryption tests use.
+    pub key_vault_data: Option<Vec<Document>>,
+    #[serde(default)]
+    pub data: TestData,
+    pub tests: Vec<Test>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RunOn {
+    pub min_server_version: Option<String>,
+    pub max_server_version: Option<String>,
+    pub topology: Option<Vec<String>>,
+    pub serverless: Option<Serverless>,
+    pub auth_enabled: Option<bool>,
+}
+
+impl RunOn {
+    /// Describes any problems with this entry's server version bounds.
+    pub(crate) fn server_version_problems(&self) -> Vec<String> {
+        server_version_problems(
+            self.min_server_version.as_deref(),
+            self.max_server_version.as_deref(),
+        )
+    }
+}
+
+/// Checks that the given bounds parse as server versions and that the minimum doesn't exceed
+/// the maximum, since either mistake means the converted test never runs.
+fn server_version_problems(min: Option<&str>, max: Option<&str>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut parse = |field: &str, version: Option<&str>| {
+        let version = version?;
+        let parsed = parse_server_version(version);
+        if parsed.is_none() {
+            problems.push(format!("{} {:?} is not a valid server version", field, version));
+        }
+        parsed
+    };
+    let min_parsed = parse("minServerVersion", min);
+    let max_parsed = parse("maxServerVersion", max);
+
+    if let (Some(min_parsed), Some(max_parsed)) = (min_parsed, max_parsed) {
+        if min_parsed > max_parsed {
+            problems.push(format!(
+                "minServerVersion {} is greater than maxServerVersion {}",
+                min.unwrap_or_default(),
+                max.unwrap_or_default()
+            ));
+        }
+    }
+    problems
+}
+
+/// Parses a server version like "4.4" or "4.2.1" into its components, padded to three so that
+/// versions with different numbers of components compare correctly.
+fn parse_server_version(version: &str) -> Option<Vec<u64>> {
+    let mut components = version
+        .split('.')
+        .map(|component| component.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    if components.len() > 3 {
+        return None;
+    }
+    components.resize(3, 0);
+    Some(components)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TestData {
+    Single(Vec<Document>),
+    Many(BTreeMap<String, Vec<Document>>),
+}
+
+/// Change streams tests don't have any data.
+impl Default for TestData {
+    fn default() -> Self {
+        Self::Single(Vec::new())
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Test {
+    pub description: String,
+    pub run_on: Option<Vec<RunOn>>,
+    pub min_server_version: Option<String>,
+    pub max_server_version: Option<String>,
+    pub topology: Option<Vec<String>>,
+    pub skip_reason: Option<String>,
+    pub use_multiple_mongoses: Option<bool>,
+    #[serde(default, rename = "clientOptions")]
+    pub client_uri: Option<Document>,
+    pub fail_point: Option<Document>,
+    pub session_options: Option<BTreeMap<String, Document>>,
+    pub operations: Vec<Operation>,
+    pub expectations: Option<Vec<Expectation>>,
+    pub outcome: Option<Outcome>,
+
+    /// What change streams tests watch before running their operations.
+    pub target: Option<ChangeStreamTarget>,
+    pub change_stream_pipeline: Option<Vec<Document>>,
+    pub change_stream_options: Option<Document>,
+
+    /// The changes change streams tests expect to see after running their operations, or the
+    /// error they expect instead.
+    pub result: Option<ChangeStreamResult>,
+}
+
+impl Test {
+    pub(crate) fn observed_events(&self) -> HashSet<&'static str> {
+        let mut observe_events = HashSet::new();
+        for event in self.expectations.iter().flatten() {
+            observe_events.insert(event.unified_name());
+        }
+        // an empty expectations list asserts that no commands were sent, which only means
+        // something if they're observed.
+        if matches!(self.expectations, Some(ref expectations) if expectations.is_empty()) {
+            observe_events.insert("commandStartedEvent");
+        }
+        // events waited on or counted by the test runner have to be observed too.
+        for op in self.operations.iter().filter(|op| {
+            matches!(op.name.as_str(), "waitForEvent" | "assertEventCount")
+        }) {
+            let event = op.arguments.as_ref().and_then(|a| a.get_str("event").ok());
+            if let Some(event) = event.and_then(|event| {
+                CmapEvent::unified_name_of(event).or_else(|| SdamEvent::unified_name_of(event))
+            }) {
+                observe_events.insert(event);
+            }
+        }
+        // the unified lsid assertions inspect the client's observed command started events.
+        if self.operations.iter().any(|op| {
+            matches!(
+                op.name.as_str(),
+                "assertSameLsidOnLastTwoCommands" | "assertDifferentLsidOnLastTwoCommands"
+            )
+        }) {
+            observe_events.insert("commandStartedEvent");
+        }
+        observe_events
+    }
+
+    /// The names of the sessions this test refers to, either via its session options or its
+    /// operations' objects and `session` arguments, including those of nested operations. Sessions its expectations assert the lsids
+    /// of are only known once they're converted.
+    pub(crate) fn session_names(&self) -> BTreeSet<String> {
+        let mut names: BTreeSet<String> = self
+            .session_options
+            .iter()
+            .flat_map(|options| options.keys().cloned())
+            .collect();
+        for op in self.operations.iter() {
+            op.add_session_names(&mut names);
+        }
+        names
+    }
+
+    /// The names of the threads this test starts, in the order they're started.
+    pub(crate) fn thread_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for op in self.operations.iter().filter(|op| op.name == "startThread") {
+            if let Some(Ok(name)) = op.arguments.as_ref().map(|a| a.get_str("name")) {
+                if !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names
+    }
+
+    /// Describes any problems with the server version bounds in this test's requirements.
+    pub(crate) fn server_version_problems(&self) -> Vec<String> {
+        let mut problems = server_version_problems(
+            self.min_server_version.as_deref(),
+            self.max_server_version.as_deref(),
+        );
+        problems.extend(self.run_on.iter().flatten().flat_map(RunOn::server_version_problems));
+        problems
+    }
+
+    /// Whether any of this test's expectations are about security-sensitive commands.
+    pub(crate) fn expects_sensitive_commands(&self) -> bool {
+        self.expectations
+            .iter()
+            .flatten()
+            .filter_map(Expectation::as_command)
+            .any(CommandEvent::is_sensitive)
+    }
+
+    /// The requirements this test places on the deployment it runs against, whether they were
+    /// specified via a `runOn` list or directly on the test.
+    pub(crate) fn run_on(&mut self) -> Option<Vec<RunOn>> {
+        if let Some(run_on) = self.run_on.take() {
+            return Some(run_on);
+        }
+
+        if self.min_server_version.is_none()
+            && self.max_server_version.is_none()
+            && self.topology.is_none()
+        {
+            return None;
+        }
+
+        Some(vec![RunOn {
+            min_server_version: self.min_server_version.take(),
+            max_server_version: self.max_server_version.take(),
+            topology: self.topology.take(),
+            serverless: None,
+            auth_enabled: None,
+        }])
+    }
+}
+
+/// An event that a legacy test's `expectations` list says should be observed.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Expectation {
+    Command(CommandEvent),
+    Cmap(CmapEvent),
+    Sdam(SdamEvent),
+}
+
+impl Expectation {
+    /// The name of this event's type in the unified format.
+    pub(crate) fn unified_name(&self) -> &'static str {
+        match self {
+            Self::Command(event) => event.unified_name(),
+            Self::Cmap(event) => event.unified_name(),
+            Self::Sdam(event) => event.unified_name(),
+        }
+    }
+
+    pub(crate) fn as_command(&self) -> Option<&CommandEvent> {
+        match self {
+            Self::Command(event) => Some(event),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum CmapEvent {
+    #[serde(rename = "pool_cleared_event")]
+    PoolCleared(Document),
+    #[serde(rename = "pool_ready_event")]
+    PoolReady(Document),
+    #[serde(rename = "connection_created_event")]
+    ConnectionCreated(Document),
+}
+
+impl CmapEvent {
+    /// The name of this event's type in the unified format.
+    pub(crate) fn unified_name(&self) -> &'static str {
+        match self {
+            Self::PoolCleared(_) => "poolClearedEvent",
+            Self::PoolReady(_) => "poolReadyEvent",
+            Self::ConnectionCreated(_) => "connectionCreatedEvent",
+        }
+    }
+
+    /// The unified name of a CMAP event given the legacy test runner's name for it (e.g. in
+    /// `waitForEvent` arguments).
+    pub(crate) fn unified_name_of(runner_name: &str) -> Option<&'static str> {
+        match runner_name {
+            "PoolClearedEvent" => Some("poolClearedEvent"),
+            "PoolReadyEvent" => Some("poolReadyEvent"),
+            "ConnectionCreatedEvent" => Some("connectionCreatedEvent"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum SdamEvent {
+    #[serde(rename = "server_description_changed_event")]
+    ServerDescriptionChanged(Document),
+    #[serde(rename = "topology_description_changed_event")]
+    TopologyDescriptionChanged(Document),
+}
+
+impl SdamEvent {
+    /// The name of this event's type in the unified format.
+    pub(crate) fn unified_name(&self) -> &'static str {
+        match self {
+            Self::ServerDescriptionChanged(_) => "serverDescriptionChangedEvent",
+            Self::TopologyDescriptionChanged(_) => "topologyDescriptionChangedEvent",
+        }
+    }
+
+    /// The unified name of the SDAM event observed for the legacy test runner's name for an
+    /// event (e.g. in `waitForEvent` arguments).
+    pub(crate) fn unified_name_of(runner_name: &str) -> Option<&'static str> {
+        match runner_name {
+            "ServerMarkedUnknownEvent" => Some("serverDescriptionChangedEvent"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum CommandEvent {
+    #[serde(rename = "command_started_event")]
+    Started(CommandStartedEvent),
+    #[serde(rename = "command_succeeded_event")]
+    Succeeded(CommandSucceededEvent),
+    #[serde(rename = "command_failed_event")]
+    Failed(CommandFailedEvent),
+}
+
+impl CommandEvent {
+    /// The name of this event's type in the unified format.
+    pub(crate) fn unified_name(&self) -> &'static str {
+        match self {
+            Self::Started(_) => "commandStartedEvent",
+            Self::Succeeded(_) => "commandSucceededEvent",
+            Self::Failed(_) => "commandFailedEvent",
+        }
+    }
+
+    fn command_name(&self) -> Option<&str> {
+        match self {
+            Self::Started(event) => event
+                .command_name
+                .as_deref()
+                .or_else(|| event.command.keys().next().map(String::as_str)),
+            Self::Succeeded(event) => event.command_name.as_deref(),
+            Self::Failed(event) => event.command_name.as_deref(),
+        }
+    }
+
+    /// Whether this event is for a command that is only observed when a client has
+    /// `observeSensitiveCommands` set.
+    pub(crate) fn is_sensitive(&self) -> bool {
+        let name = match self.command_name() {
+            Some(name) => name,
+            None => return false,
+        };
+
+        if SENSITIVE_COMMANDS
+            .iter()
+            .any(|sensitive| sensitive.eq_ignore_ascii_case(name))
+        {
+            return true;
+        }
+
+        // hello commands are only sensitive when they include speculative authentication.
+        match self {
+            Self::Started(event)
+                if name.eq_ignore_ascii_case("hello") || name.eq_ignore_ascii_case("isMaster") =>
+            {
+                event.command.contains_key("speculativeAuthenticate")
+            }
+            _ => false,
+        }
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandStartedEvent {
+    pub command_name: Option<String>,
+    pub database_name: Option<String>,
+    pub command: Document,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandSucceededEvent {
+    pub command_name: Option<String>,
+    pub reply: Option<Document>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandFailedEvent {
+    pub command_name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeStreamTarget {
+    Client,
+    Database,
+    Collection,
+}
+
+impl ChangeStreamTarget {
+    /// The legacy object that a change stream on this target is opened on.
+    pub(crate) fn object(self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Database => "database",
+            Self::Collection => "collection",
+        }
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChangeStreamResult {
+    pub success: Option<Vec<Document>>,
+
+    /// The error's `code`, `codeName`, and `errorLabels`.
+    pub error: Option<Document>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Outcome {
+    pub collection: CollectionOutcome,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CollectionOutcome {
+    pub name: Option<String>,
+    pub data: TestData,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Operation {
+    pub name: String,
+    #[serde(default = "default_object")]
+    pub object: String,
+
+    /// The namespace change streams tests' operations run in, which defaults to the file's.
+    pub database: Option<String>,
+    pub collection: Option<String>,
+    // this field is required only for the runAdminCommand operation
+    #[serde(rename = "command_name")]
+    pub command_name: Option<String>,
+    pub arguments: Option<Document>,
+    pub collection_options: Option<Document>,
+    pub database_options: Option<Document>,
+    pub error: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_result")]
+    pub result: Option<OperationResult>,
+}
+
+/// Change streams tests' operations don't have an object, since they all run on collections.
+fn default_object() -> String {
+    "collection".to_string()
+}
+
+impl Operation {
+    /// Whether this operation is, or runs, one with the given name, e.g. in a `withTransaction`
+    /// callback.
+    pub(crate) fn runs(&self, name: &str) -> bool {
+        self.name == name || self.nested().iter().any(|op| op.runs(name))
+    }
+
+    /// Adds the names of the sessions this operation, or any it runs, refers to via its object
+    /// or `session` argument.
+    fn add_session_names(&self, names: &mut BTreeSet<String>) {
+        if let Some(Ok(name)) = self.arguments.as_ref().map(|a| a.get_str("session")) {
+            names.insert(name.to_string());
+        }
+        if self.object.starts_with("session") {
+            names.insert(self.object.clone());
+        }
+        for op in self.nested() {
+            op.add_session_names(names);
+        }
+    }
+
+    /// The operations nested in this one's arguments, i.e. those in a `withTransaction` callback
+    /// or the one `runOnThread` runs.
+    fn nested(&self) -> Vec<Operation> {
+        let arguments = match self.arguments {
+            Some(ref arguments) => arguments,
+            None => return Vec::new(),
+        };
+        let nested: Vec<&Bson> = match self.name.as_str() {
+            "withTransaction" => arguments
+                .get_document("callback")
+                .and_then(|callback| callback.get_array("operations"))
+                .map(|operations| operations.iter().collect())
+                .unwrap_or_default(),
+            "runOnThread" => arguments.get("operation").into_iter().collect(),
+            _ => Vec::new(),
+        };
+        nested
+            .into_iter()
+            .filter_map(|op| bson::from_bson(op.clone()).ok())
+            .collect()
+    }
+
+    /// The database, if it's given, and the collection that this operation writes to if it's an
+    /// aggregation ending in a `$out` or `$merge` stage.
+    pub(crate) fn aggregation_output(&self) -> Option<(Option<String>, String)> {
+        if self.name != "aggregate" {
+            return None;
+        }
+        let pipeline = self.arguments.as_ref()?.get_array("pipeline").ok()?;
+        let stage = pipeline.last()?.as_document()?;
+        let target = match (stage.get("$out"), stage.get("$merge")) {
+            (Some(out), _) => out,
+            (None, Some(Bson::Document(merge))) => merge.get("into")?,
+            (None, Some(into)) => into,
+            (None, None) => return None,
+        };
+        match target {
+            Bson::String(collection) => Some((None, collection.clone())),
+            Bson::Document(namespace) => Some((
+                namespace.get_str("db").ok().map(String::from),
+                namespace.get_str("coll").ok()?.to_string(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum OperationResult {
+    Error(OperationError),
+    Success(Bson),
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OperationError {
+    pub error_contains: Option<String>,
+    pub error_code_name: Option<String>,
+    pub error_code: Option<i32>,
+    pub error_labels_contain: Option<Vec<String>>,
+    pub error_labels_omit: Option<Vec<String>>,
+    pub error_response: Option<Document>,
+}
+
+
+/// Deserializes an operation's result, converting any integer map keys (e.g. legacy
+/// `insertedIds: { 0: 1 }`) into the string keys BSON documents require.
+fn deserialize_result<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<OperationResult>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut value = Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return Ok(None);
+    }
+    stringify_keys(&mut value);
+    serde_yaml::from_value(value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn stringify_keys(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    stringify_keys(&mut value);
+                    let key = match key {
+                        Value::Number(n) => Value::String(n.to_string()),
+                        key => key,
+                    };
+                    (key, value)
+                })
+                .collect();
+        }
+        Value::Sequence(values) => values.iter_mut().for_each(stringify_keys),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Serverless {
+    Require,
+    Forbid,
+    Allow,
+}
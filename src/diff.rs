@@ -0,0 +1,133 @@
+//! Structural comparison of unified test files, for checking converted files against existing
+//! hand-written ones.
+//!
+//! Files are compared after YAML aliases have been resolved, so anchor names and which values
+//! were anchored don't matter, and mappings are compared regardless of key order.
+
+use std::fmt;
+
+use serde_yaml::Value;
+
+use crate::ConversionError;
+
+/// A way in which one unified test file differs from another.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// A value that's only in the expected file.
+    Missing { path: String, expected: Value },
+
+    /// A value that's only in the actual file.
+    Unexpected { path: String, actual: Value },
+
+    /// A value that's in both files but differs.
+    Changed {
+        path: String,
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { path, expected } => write!(f, "- {}: {}", path, compact(expected)),
+            Self::Unexpected { path, actual } => write!(f, "+ {}: {}", path, compact(actual)),
+            Self::Changed {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "~ {}: expected {}, got {}",
+                path,
+                compact(expected),
+                compact(actual)
+            ),
+        }
+    }
+}
+
+/// Renders a value on a single line.
+fn compact(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+/// Compares the YAML contents of two unified test files.
+pub fn diff_str(expected: &str, actual: &str) -> Result<Vec<Difference>, ConversionError> {
+    let expected = serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(expected))?;
+    let actual = serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(actual))?;
+    Ok(diff(&expected, &actual))
+}
+
+/// The differences between two parsed YAML documents, in document order.
+pub fn diff(expected: &Value, actual: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_at(String::new(), expected, actual, &mut differences);
+    differences
+}
+
+fn diff_at(path: String, expected: &Value, actual: &Value, differences: &mut Vec<Difference>) {
+    match (expected, actual) {
+        (Value::Mapping(expected), Value::Mapping(actual)) => {
+            for (key, expected) in expected {
+                let path = key_path(&path, key);
+                match actual.get(key) {
+                    Some(actual) => diff_at(path, expected, actual, differences),
+                    None => differences.push(Difference::Missing {
+                        path,
+                        expected: expected.clone(),
+                    }),
+                }
+            }
+            for (key, actual) in actual {
+                if !expected.contains_key(key) {
+                    differences.push(Difference::Unexpected {
+                        path: key_path(&path, key),
+                        actual: actual.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Sequence(expected), Value::Sequence(actual)) => {
+            for (i, expected) in expected.iter().enumerate() {
+                let path = format!("{}[{}]", path, i);
+                match actual.get(i) {
+                    Some(actual) => diff_at(path, expected, actual, differences),
+                    None => differences.push(Difference::Missing {
+                        path,
+                        expected: expected.clone(),
+                    }),
+                }
+            }
+            for (i, actual) in actual.iter().enumerate().skip(expected.len()) {
+                differences.push(Difference::Unexpected {
+                    path: format!("{}[{}]", path, i),
+                    actual: actual.clone(),
+                });
+            }
+        }
+        (expected, actual) if expected != actual => differences.push(Difference::Changed {
+            path: if path.is_empty() {
+                ".".to_string()
+            } else {
+                path
+            },
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// The path to a mapping value, e.g. `tests[3].operations`.
+fn key_path(parent: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        key => compact(key),
+    };
+    if parent.is_empty() {
+        key
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
@@ -0,0 +1,679 @@
+//! Conversion of simple unified test files back into the legacy v2 format, for test runners
+//! that haven't moved to the unified format yet.
+//!
+//! Only files that stick to what the legacy format can express are supported: one test client,
+//! database and collection, sessions, the runner operations the legacy format has, and the
+//! matchers the converter itself emits. Anything else fails with
+//! [`ConversionError::Inexpressible`].
+
+use std::collections::BTreeMap;
+
+use bson::{doc, Bson, Document};
+
+use crate::{
+    crud_v2::{
+        self,
+        CmapEvent,
+        CommandEvent,
+        Expectation,
+        OperationResult,
+        SdamEvent,
+        TestData,
+        KEY_VAULT_NAMESPACE,
+    },
+    extended_json,
+    unified::{
+        self,
+        CONNECTION_CREATED,
+        CreateEntity,
+        ExpectEvent,
+        InitialData,
+        KNOWN_OPERATIONS,
+        POOL_CLEARED,
+        POOL_READY,
+        SERVER_DESCRIPTION_CHANGED,
+    },
+    ConversionError,
+};
+
+/// Converts the YAML contents of a unified test file into a legacy test file.
+pub fn downgrade_str(input: &str) -> Result<String, ConversionError> {
+    let file: unified::TestFile = extended_json::from_str(input)?;
+    let old = downgrade(file)?;
+    serde_yaml::to_string(&extended_json::to_value(&old)?).map_err(ConversionError::Emit)
+}
+
+/// Converts a parsed unified test file into a legacy test file.
+pub fn downgrade(file: unified::TestFile) -> Result<crud_v2::TestFile, ConversionError> {
+    let mut entities = Entities::default();
+    for entity in file.create_entities.into_iter().flatten() {
+        entities.add(bson::from_bson(entity)?)?;
+    }
+
+    let mut tests = Vec::new();
+    for test in file.tests {
+        let description = test.description.clone();
+        tests.push(downgrade_test(test, entities.clone()).map_err(|e| e.in_test(description))?);
+    }
+    // tests may create their own entities, so the namespace is only known once they've been
+    // downgraded.
+    let (database_name, collection_name) = tests
+        .iter()
+        .map(|(_, entities)| entities)
+        .chain(std::iter::once(&entities))
+        .find_map(|entities| {
+            Some((
+                entities.databases.first()?.name.clone(),
+                entities.collections.first()?.name.clone(),
+            ))
+        })
+        .ok_or_else(|| inexpressible("files without a database and collection entity"))?;
+
+    let mut key_vault_data = None;
+    let (create_options, data) = match file.initial_data {
+        Some(mut initial_data) => {
+            if let Some(i) = initial_data.iter().position(|data| {
+                (data.database_name.as_str(), data.collection_name.as_str()) == KEY_VAULT_NAMESPACE
+            }) {
+                key_vault_data = Some(initial_data.remove(i).documents);
+            }
+            let create_options = match initial_data.as_slice() {
+                [data] => data.create_options.clone(),
+                _ => None,
+            };
+            (create_options, test_data(initial_data, &collection_name))
+        }
+        None => (None, TestData::Single(Vec::new())),
+    };
+
+    Ok(crud_v2::TestFile {
+        run_on: file.run_on_requirements.map(run_on).transpose()?,
+        database_name: Some(database_name),
+        collection_name,
+        database2_name: None,
+        collection2_name: None,
+        bucket_name: None,
+        create_options,
+        json_schema: None,
+        encrypted_fields: None,
+        key_vault_data,
+        data,
+        tests: tests.into_iter().map(|(test, _)| test).collect(),
+    })
+}
+
+/// The entities available to a test. The legacy format only has one of each, plus the admin
+/// database that `runAdminCommand` uses. Further databases and collections are only expressible
+/// as per-operation `databaseOptions` and `collectionOptions` on the test's namespace.
+#[derive(Clone, Default)]
+struct Entities {
+    /// The ids and `uriOptions` of every client, including ones only used to configure fail
+    /// points.
+    clients: BTreeMap<String, Option<Document>>,
+    /// The clients that set `useMultipleMongoses`, and what to.
+    use_multiple_mongoses: BTreeMap<String, bool>,
+    databases: Vec<NamespaceEntity>,
+    admin_database: Option<String>,
+    collections: Vec<NamespaceEntity>,
+    bucket: Option<String>,
+    sessions: Vec<String>,
+    /// The `sessionOptions` of the sessions that have them.
+    session_options: BTreeMap<String, Document>,
+}
+
+/// A database or collection entity.
+#[derive(Clone)]
+struct NamespaceEntity {
+    id: String,
+    name: String,
+    /// The id of the client or database the entity was created from.
+    parent: String,
+    options: Option<Document>,
+}
+
+/// The legacy object an operation runs on, along with the options of the entity it ran on in
+/// the unified file.
+struct LegacyObject {
+    object: String,
+    database_options: Option<Document>,
+    collection_options: Option<Document>,
+}
+
+impl Entities {
+    fn add(&mut self, entity: CreateEntity) -> Result<(), ConversionError> {
+        match entity {
+            CreateEntity::Client(client) => {
+                if let Some(use_multiple_mongoses) = client.use_multiple_mongoses {
+                    self.use_multiple_mongoses
+                        .insert(client.id.clone(), use_multiple_mongoses);
+                }
+                self.clients.insert(client.id, client.uri_options);
+            }
+            CreateEntity::Database(database)
+                if database.database_name == "admin" && self.admin_database.is_none() =>
+            {
+                self.admin_database = Some(database.id);
+            }
+            CreateEntity::Database(database) => {
+                check_namespace(&self.databases, &database.database_name)?;
+                self.databases.push(NamespaceEntity {
+                    id: database.id,
+                    name: database.database_name,
+                    parent: database.client,
+                    options: database.database_options,
+                });
+            }
+            CreateEntity::Collection(collection) => {
+                check_namespace(&self.collections, &collection.collection_name)?;
+                self.collections.push(NamespaceEntity {
+                    id: collection.id,
+                    name: collection.collection_name,
+                    parent: collection.database,
+                    options: collection.collection_options,
+                });
+            }
+            CreateEntity::Bucket(bucket) if self.bucket.is_none() => {
+                self.bucket = Some(bucket.id);
+            }
+            CreateEntity::Session(session) if session.id.starts_with("session") => {
+                if let Some(options) = session.session_options {
+                    self.session_options.insert(session.id.clone(), options);
+                }
+                self.sessions.push(session.id);
+            }
+            CreateEntity::Session(session) => {
+                return Err(inexpressible(format!(
+                    "session {}, since legacy session names must start with \"session\"",
+                    session.id
+                )))
+            }
+            CreateEntity::Thread { .. } => {
+                return Err(inexpressible("threads created before the test starts"))
+            }
+            CreateEntity::Bucket(_) => return Err(inexpressible("more than one bucket")),
+        }
+        Ok(())
+    }
+
+    /// The id of the client that the test's operations run against: the one the test's
+    /// database belongs to, or the only client there is.
+    fn test_client(&self) -> Option<&str> {
+        match self.databases.first() {
+            Some(database) => Some(database.parent.as_str()),
+            None if self.clients.len() == 1 => self.clients.keys().next().map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// The legacy object for an operation on the entity with the given id.
+    fn object(&self, id: &str) -> Result<LegacyObject, ConversionError> {
+        let mut database_options = None;
+        let mut collection_options = None;
+        let database = |id: &str| self.databases.iter().find(|database| database.id == id);
+        let object = if id == "testRunner" {
+            "testRunner"
+        } else if self.test_client() == Some(id) {
+            "client"
+        } else if let Some(database) = database(id) {
+            database_options = database.options.clone();
+            "database"
+        } else if let Some(collection) = self.collections.iter().find(|c| c.id == id) {
+            database_options = database(&collection.parent).and_then(|d| d.options.clone());
+            collection_options = collection.options.clone();
+            "collection"
+        } else if self.bucket.as_deref() == Some(id) {
+            "gridfsbucket"
+        } else if self.sessions.iter().any(|session| session == id) {
+            id
+        } else {
+            return Err(inexpressible(format!("operations on {}", id)));
+        };
+        Ok(LegacyObject {
+            object: object.to_string(),
+            database_options,
+            collection_options,
+        })
+    }
+}
+
+/// Checks that a database or collection has the same name as the test's, since the legacy
+/// format only has the one namespace.
+fn check_namespace(existing: &[NamespaceEntity], name: &str) -> Result<(), ConversionError> {
+    match existing.first() {
+        Some(first) if first.name != name => Err(inexpressible(format!(
+            "both {} and {}, since the legacy format only has one namespace",
+            first.name, name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn downgrade_test(
+    test: unified::Test,
+    mut entities: Entities,
+) -> Result<(crud_v2::Test, Entities), ConversionError> {
+    // the legacy format creates every entity before the test starts, so entities created part
+    // way through are hoisted.
+    let mut fail_point = None;
+    let mut operations = Vec::new();
+    for (i, op) in test.operations.into_iter().enumerate() {
+        match op.name.as_str() {
+            "createEntities" => {
+                let created = op
+                    .arguments
+                    .and_then(|mut arguments| arguments.remove("entities"))
+                    .ok_or(ConversionError::MissingArgument {
+                        operation: op.name,
+                        argument: "entities",
+                    })?;
+                let created: Vec<CreateEntity> = bson::from_bson(created)?;
+                for entity in created {
+                    // threads are started where they're created rather than being hoisted.
+                    if let CreateEntity::Thread { id } = entity {
+                        operations.push((
+                            i,
+                            unified::Operation {
+                                name: "startThread".to_string(),
+                                object: "testRunner".to_string(),
+                                arguments: Some(doc! { "thread": id }),
+                                ..Default::default()
+                            },
+                        ));
+                        continue;
+                    }
+                    entities.add(entity).map_err(|e| e.in_operation(&test.description, i))?;
+                }
+            }
+            "failPoint" if fail_point.is_none() && operations.is_empty() => {
+                fail_point = Some(
+                    fail_point_argument(op).map_err(|e| e.in_operation(&test.description, i))?,
+                );
+            }
+            _ => operations.push((i, op)),
+        }
+    }
+    let operations = operations
+        .into_iter()
+        .map(|(i, op)| {
+            downgrade_operation(op, &entities).map_err(|e| e.in_operation(&test.description, i))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut expectations = None;
+    for expected in test.expect_events.into_iter().flatten() {
+        if entities.test_client() != Some(expected.client.as_str()) {
+            return Err(inexpressible(format!("events expected on {}", expected.client)));
+        }
+        let expectations = expectations.get_or_insert_with(Vec::new);
+        for event in expected.events {
+            expectations.push(expectation(event)?);
+        }
+    }
+
+    let collection_name = entities
+        .collections
+        .first()
+        .map(|collection| collection.name.clone())
+        .unwrap_or_default();
+    let outcome = test.outcome.map(|outcome| crud_v2::Outcome {
+        collection: crud_v2::CollectionOutcome {
+            name: None,
+            data: test_data(outcome, &collection_name),
+        },
+    });
+    let client_uri = entities
+        .test_client()
+        .and_then(|id| entities.clients.get(id).cloned().flatten());
+    let use_multiple_mongoses = entities
+        .test_client()
+        .and_then(|id| entities.use_multiple_mongoses.get(id).copied());
+
+    let test = crud_v2::Test {
+        description: test.description,
+        run_on: test.run_on_requirements.map(run_on).transpose()?,
+        min_server_version: None,
+        max_server_version: None,
+        topology: None,
+        skip_reason: test.skip_reason,
+        use_multiple_mongoses,
+        client_uri,
+        fail_point,
+        session_options: Some(entities.session_options.clone())
+            .filter(|options| !options.is_empty()),
+        operations,
+        expectations,
+        outcome,
+        target: None,
+        change_stream_pipeline: None,
+        change_stream_options: None,
+        result: None,
+    };
+    Ok((test, entities))
+}
+
+fn downgrade_operation(
+    op: unified::Operation,
+    entities: &Entities,
+) -> Result<crud_v2::Operation, ConversionError> {
+    if op.save_result_as_entity.is_some() {
+        return Err(inexpressible(format!("saving the result of {}", op.name)));
+    }
+    let mut name = op.name;
+    let mut object = op.object;
+    let mut arguments = op.arguments;
+    let mut command_name = None;
+    if name == "runCommand" {
+        command_name = arguments
+            .as_mut()
+            .and_then(|arguments| arguments.remove("commandName"))
+            .and_then(|name| name.as_str().map(String::from));
+        if entities.admin_database.as_ref() == Some(&object) {
+            name = "runAdminCommand".to_string();
+            object = "testRunner".to_string();
+        }
+    } else if name == "runOnThread" {
+        let mut thread_arguments = arguments.unwrap_or_default();
+        let operation = thread_arguments.remove("operation").ok_or_else(|| {
+            ConversionError::MissingArgument {
+                operation: name.clone(),
+                argument: "operation",
+            }
+        })?;
+        let operation = downgrade_operation(bson::from_bson(operation)?, entities)?;
+        arguments = Some(doc! {
+            "name": thread_arguments.remove("thread").unwrap_or(Bson::Null),
+            "operation": bson::to_bson(&operation)?,
+        });
+    } else if object == "testRunner" {
+        let (legacy_name, legacy_arguments) = test_runner_operation(&name, arguments)?;
+        name = legacy_name.to_string();
+        arguments = legacy_arguments;
+    }
+    if let Some(ref mut arguments) = arguments {
+        unwrap_matchers(arguments)?;
+    }
+
+    let (error, result) = match (op.expect_result, op.expect_error) {
+        (Some(mut result), None) => {
+            unwrap_matchers_in(&mut result)?;
+            (None, Some(OperationResult::Success(result)))
+        }
+        (None, Some(expect_error)) => {
+            // error results imply an error in the legacy format, so `error: true` is only needed
+            // when the unified file asserts it explicitly or doesn't assert anything else.
+            let is_error = expect_error.is_error;
+            let result = match expect_error.expect_result {
+                Some(mut result) => {
+                    unwrap_matchers_in(&mut result)?;
+                    Some(OperationResult::Success(result))
+                }
+                None => {
+                    let error = crud_v2::OperationError {
+                        error_contains: expect_error.error_contains,
+                        error_code_name: expect_error.error_code_name,
+                        error_code: expect_error.error_code,
+                        error_labels_contain: expect_error.error_labels_contain,
+                        error_labels_omit: expect_error.error_labels_omit,
+                        error_response: expect_error.error_response,
+                    };
+                    let asserts_anything = error.error_contains.is_some()
+                        || error.error_code_name.is_some()
+                        || error.error_code.is_some()
+                        || error.error_labels_contain.is_some()
+                        || error.error_labels_omit.is_some()
+                        || error.error_response.is_some();
+                    asserts_anything.then_some(OperationResult::Error(error))
+                }
+            };
+            let error = match result {
+                Some(OperationResult::Error(_)) => is_error,
+                _ => Some(true),
+            };
+            (error, result)
+        }
+        (None, None) => (None, None),
+        (Some(_), Some(_)) => {
+            return Err(inexpressible(format!(
+                "{} expecting both a result and an error",
+                name
+            )))
+        }
+    };
+
+    let object = entities.object(&object)?;
+    Ok(crud_v2::Operation {
+        object: object.object,
+        database: None,
+        collection: None,
+        name,
+        command_name,
+        arguments,
+        collection_options: object.collection_options,
+        database_options: object.database_options,
+        error,
+        result,
+    })
+}
+
+/// The legacy name and arguments of a test runner operation, undoing what
+/// `operations::test_runner` does when converting them.
+fn test_runner_operation(
+    name: &str,
+    arguments: Option<Document>,
+) -> Result<(&str, Option<Document>), ConversionError> {
+    let mut arguments = arguments.unwrap_or_default();
+    let (name, arguments) = match name {
+        "failPoint" => (
+            "configureFailPoint",
+            doc! { "failPoint": fail_point_argument(unified::Operation {
+                name: name.to_string(),
+                arguments: Some(arguments),
+                ..Default::default()
+            })? },
+        ),
+        "waitForEvent" | "assertEventCount" => {
+            let event = arguments.get_document("event").ok().and_then(legacy_event_name);
+            let event = event.ok_or_else(|| inexpressible(format!("the event {}", name)))?;
+            let count = arguments.remove("count").ok_or(ConversionError::MissingArgument {
+                operation: name.to_string(),
+                argument: "count",
+            })?;
+            (name, doc! { "event": event, "count": count })
+        }
+        "recordTopologyDescription" => ("recordPrimary", Document::new()),
+        "waitForPrimaryChange" => {
+            let mut legacy = Document::new();
+            if let Some(timeout) = arguments.remove("timeoutMS") {
+                legacy.insert("timeoutMS", timeout);
+            }
+            (name, legacy)
+        }
+        "assertCollectionExists"
+        | "assertCollectionNotExists"
+        | "assertIndexExists"
+        | "assertIndexNotExists" => {
+            let mut legacy = Document::new();
+            for (unified, legacy_name) in [
+                ("databaseName", "database"),
+                ("collectionName", "collection"),
+                ("indexName", "index"),
+            ] {
+                if let Some(value) = arguments.remove(unified) {
+                    legacy.insert(legacy_name, value);
+                }
+            }
+            (name, legacy)
+        }
+        // threads are referred to by name rather than entity id.
+        "startThread" | "waitForThread" => {
+            let thread = arguments.remove("thread").ok_or(ConversionError::MissingArgument {
+                operation: name.to_string(),
+                argument: "thread",
+            })?;
+            (name, doc! { "name": thread })
+        }
+        // the remaining legacy runner operations take the same arguments in both formats.
+        name if KNOWN_OPERATIONS.contains(&name) => (name, arguments),
+        _ => return Err(inexpressible(format!("the runner operation {}", name))),
+    };
+    Ok((name, (!arguments.is_empty()).then_some(arguments)))
+}
+
+/// The legacy name of an event matched by `waitForEvent` or `assertEventCount`.
+fn legacy_event_name(event: &Document) -> Option<&'static str> {
+    let (name, fields) = event.iter().next()?;
+    let name = match name.as_str() {
+        name if name == SERVER_DESCRIPTION_CHANGED
+            && fields.as_document()?.get_document("newDescription").ok()?.get_str("type")
+                == Ok("Unknown") =>
+        {
+            "ServerMarkedUnknownEvent"
+        }
+        name if name == POOL_CLEARED => "PoolClearedEvent",
+        name if name == POOL_READY => "PoolReadyEvent",
+        name if name == CONNECTION_CREATED => "ConnectionCreatedEvent",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The fail point document of a unified `failPoint` operation, which the legacy format
+/// configures on the test itself or via `configureFailPoint`.
+fn fail_point_argument(op: unified::Operation) -> Result<Document, ConversionError> {
+    op.arguments
+        .and_then(|mut arguments| arguments.remove("failPoint"))
+        .and_then(|fail_point| match fail_point {
+            Bson::Document(fail_point) => Some(fail_point),
+            _ => None,
+        })
+        .ok_or(ConversionError::MissingArgument {
+            operation: op.name,
+            argument: "failPoint",
+        })
+}
+
+fn expectation(event: ExpectEvent) -> Result<Expectation, ConversionError> {
+    let expectation = match event {
+        ExpectEvent::CommandStartedEvent {
+            mut command,
+            command_name,
+            database_name,
+        } => {
+            unwrap_matchers(&mut command)?;
+            Expectation::Command(CommandEvent::Started(crud_v2::CommandStartedEvent {
+                command_name,
+                database_name,
+                command,
+            }))
+        }
+        ExpectEvent::CommandSucceededEvent {
+            mut reply,
+            command_name,
+        } => {
+            if let Some(ref mut reply) = reply {
+                unwrap_matchers(reply)?;
+            }
+            Expectation::Command(CommandEvent::Succeeded(crud_v2::CommandSucceededEvent {
+                command_name,
+                reply,
+            }))
+        }
+        ExpectEvent::CommandFailedEvent { command_name } => {
+            Expectation::Command(CommandEvent::Failed(crud_v2::CommandFailedEvent {
+                command_name,
+            }))
+        }
+        ExpectEvent::PoolClearedEvent(event) => Expectation::Cmap(CmapEvent::PoolCleared(event)),
+        ExpectEvent::PoolReadyEvent(event) => Expectation::Cmap(CmapEvent::PoolReady(event)),
+        ExpectEvent::ConnectionCreatedEvent(event) => {
+            Expectation::Cmap(CmapEvent::ConnectionCreated(event))
+        }
+        ExpectEvent::ServerDescriptionChangedEvent(event) => {
+            Expectation::Sdam(SdamEvent::ServerDescriptionChanged(event))
+        }
+        ExpectEvent::TopologyDescriptionChangedEvent(event) => {
+            Expectation::Sdam(SdamEvent::TopologyDescriptionChanged(event))
+        }
+    };
+    Ok(expectation)
+}
+
+/// Replaces the unified matchers that the legacy format has an equivalent for, e.g.
+/// `$$unsetOrMatches`, failing on any others.
+fn unwrap_matchers(doc: &mut Document) -> Result<(), ConversionError> {
+    for (_, value) in doc.iter_mut() {
+        unwrap_matchers_in(value)?;
+    }
+    Ok(())
+}
+
+fn unwrap_matchers_in(value: &mut Bson) -> Result<(), ConversionError> {
+    match value {
+        Bson::Document(doc) => {
+            let operator = match doc.keys().next() {
+                Some(key) if key.starts_with("$$") && doc.len() == 1 => key.clone(),
+                _ => return unwrap_matchers(doc),
+            };
+            let mut operand = doc.remove(&operator).unwrap_or(Bson::Null);
+            *value = match (operator.as_str(), operand.as_bool()) {
+                ("$$unsetOrMatches", _) => {
+                    unwrap_matchers_in(&mut operand)?;
+                    operand
+                }
+                // the legacy format refers to sessions by name, which is also the session
+                // entity's id.
+                ("$$sessionLsid", _) => operand,
+                // legacy tests use 42 to match any value and null for absent fields.
+                ("$$exists", Some(true)) | ("$$type", _) => Bson::Int32(42),
+                ("$$exists", Some(false)) => Bson::Null,
+                _ => return Err(inexpressible(format!("the {} matcher", operator))),
+            };
+            Ok(())
+        }
+        Bson::Array(values) => values.iter_mut().try_for_each(unwrap_matchers_in),
+        _ => Ok(()),
+    }
+}
+
+fn run_on(
+    requirements: Vec<unified::RunOnRequirements>,
+) -> Result<Vec<crud_v2::RunOn>, ConversionError> {
+    requirements
+        .into_iter()
+        .map(|requirements| {
+            if requirements.server_parameters.is_some() {
+                return Err(inexpressible("runOnRequirements.serverParameters"));
+            }
+            if requirements.csfle.is_some() {
+                return Err(inexpressible("runOnRequirements.csfle"));
+            }
+            Ok(crud_v2::RunOn {
+                min_server_version: requirements.min_server_version,
+                max_server_version: requirements.max_server_version,
+                topology: requirements.topologies,
+                serverless: requirements.serverless,
+                auth_enabled: requirements.auth,
+            })
+        })
+        .collect()
+}
+
+/// Legacy data for the given collections, using the single collection form when only the
+/// test's collection is given.
+fn test_data(collections: Vec<InitialData>, collection_name: &str) -> TestData {
+    match collections.as_slice() {
+        [data] if data.collection_name == collection_name => {
+            TestData::Single(collections.into_iter().next().unwrap().documents)
+        }
+        _ => TestData::Many(
+            collections
+                .into_iter()
+                .map(|data| (data.collection_name, data.documents))
+                .collect(),
+        ),
+    }
+}
+
+fn inexpressible(what: impl Into<String>) -> ConversionError {
+    ConversionError::Inexpressible(what.into())
+}
@@ -0,0 +1,336 @@
+//! The entities converted files define and refer to.
+//!
+//! Entities are serialized as placeholders that are replaced by YAML anchors and aliases once a
+//! converted file has been written out, since the YAML serializer can't emit anchors itself.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bson::Bson;
+use aho_corasick::{AhoCorasick, MatchKind};
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{scalars, ConversionError};
+
+/// How the entities in converted files are named.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityNaming {
+    /// Entities are named after their type, e.g. `client` and `collection`.
+    #[default]
+    Descriptive,
+
+    /// The primary client, database, collection and bucket are numbered from zero, e.g.
+    /// `client0` and `collection0`, like most of the spec's own unified tests.
+    Numbered,
+}
+
+/// An entity, or other anchored value, that converted files define once and refer to elsewhere.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Entity {
+    Client,
+    SetupClient,
+    Database,
+    AdminDatabase,
+    Collection,
+    Bucket,
+    TopologyDescription,
+    DatabaseName,
+    CollectionName,
+    /// A database for operations that specify database options.
+    ExtraDatabase(usize),
+    /// A collection for operations that specify database or collection options.
+    ExtraCollection(usize),
+    Thread(usize),
+    ChangeStream(usize),
+    Cursor(usize),
+    Session(String),
+}
+
+impl Entity {
+    /// The entity's id in the converted file, which is also the name of its anchor.
+    pub fn id(&self, naming: EntityNaming) -> String {
+        if naming == EntityNaming::Numbered {
+            match self {
+                Self::Client => return "client0".to_string(),
+                Self::Database => return "database0".to_string(),
+                Self::Collection => return "collection0".to_string(),
+                Self::Bucket => return "bucket0".to_string(),
+                _ => {}
+            }
+        }
+        match self {
+            Self::Client => "client".to_string(),
+            Self::SetupClient => "setupClient".to_string(),
+            Self::Database => "database".to_string(),
+            Self::AdminDatabase => "adminDatabase".to_string(),
+            Self::Collection => "collection".to_string(),
+            Self::Bucket => "bucket".to_string(),
+            Self::TopologyDescription => "topologyDescription".to_string(),
+            Self::DatabaseName => "databaseName".to_string(),
+            Self::CollectionName => "collectionName".to_string(),
+            Self::ExtraDatabase(i) => format!("database{}", i),
+            Self::ExtraCollection(i) => format!("collection{}", i),
+            Self::Thread(i) => format!("thread{}", i),
+            Self::ChangeStream(i) => format!("changeStream{}", i),
+            Self::Cursor(i) => format!("cursor{}", i),
+            Self::Session(name) => name.clone(),
+        }
+    }
+
+    /// The placeholder to serialize where this entity is defined.
+    pub fn definition(&self) -> String {
+        format!("xENTITY_{}_DEFINITION", self.key())
+    }
+
+    /// The placeholder to serialize where this entity is referred to.
+    pub fn reference(&self) -> String {
+        format!("xENTITY_{}_REFERENCE", self.key())
+    }
+
+    /// Identifies the entity within its placeholders. Unlike the id, this is unambiguous, e.g.
+    /// a session can't be confused with a thread.
+    fn key(&self) -> String {
+        match self {
+            Self::ExtraDatabase(i) => format!("database-{}", i),
+            Self::ExtraCollection(i) => format!("collection-{}", i),
+            Self::Thread(i) => format!("thread-{}", i),
+            Self::ChangeStream(i) => format!("changeStream-{}", i),
+            Self::Cursor(i) => format!("cursor-{}", i),
+            Self::Session(name) => format!("session-{}", name),
+            other => other.id(EntityNaming::Descriptive),
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        let entity = match key.split_once('-') {
+            Some(("session", name)) => Self::Session(name.to_string()),
+            Some((kind, i)) => {
+                let i = i.parse().ok()?;
+                match kind {
+                    "database" => Self::ExtraDatabase(i),
+                    "collection" => Self::ExtraCollection(i),
+                    "thread" => Self::Thread(i),
+                    "changeStream" => Self::ChangeStream(i),
+                    "cursor" => Self::Cursor(i),
+                    _ => return None,
+                }
+            }
+            None => match key {
+                "client" => Self::Client,
+                "setupClient" => Self::SetupClient,
+                "database" => Self::Database,
+                "adminDatabase" => Self::AdminDatabase,
+                "collection" => Self::Collection,
+                "bucket" => Self::Bucket,
+                "topologyDescription" => Self::TopologyDescription,
+                "databaseName" => Self::DatabaseName,
+                "collectionName" => Self::CollectionName,
+                _ => return None,
+            },
+        };
+        Some(entity)
+    }
+}
+
+static PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"xENTITY_([\w-]+?)_(DEFINITION|REFERENCE)").unwrap());
+
+/// Whether two serialized values are the same once their placeholders have been replaced, e.g.
+/// the definition of an entity and a reference to it.
+pub(crate) fn same_value(a: &str, b: &str) -> bool {
+    PLACEHOLDER.replace_all(a, "xENTITY_$1") == PLACEHOLDER.replace_all(b, "xENTITY_$1")
+}
+
+/// Tracks the entities that a test, or a whole file, defines and refers to.
+#[derive(Clone, Debug, Default)]
+pub struct EntityRegistry {
+    defined: BTreeSet<Entity>,
+    referenced: BTreeSet<Entity>,
+    /// The anchored values of entities that aren't named after their id, e.g. the database name.
+    values: BTreeMap<Entity, String>,
+}
+
+impl EntityRegistry {
+    /// Records a definition of the given entity, returning the placeholder for it.
+    pub fn define(&mut self, entity: Entity) -> String {
+        let definition = entity.definition();
+        self.defined.insert(entity);
+        definition
+    }
+
+    /// Records a definition of an anchored value, e.g. the database name, returning the
+    /// placeholder for it.
+    pub fn define_value(&mut self, entity: Entity, value: impl Into<String>) -> String {
+        self.values.insert(entity.clone(), value.into());
+        self.define(entity)
+    }
+
+    /// Records a reference to the given entity, returning the placeholder for it.
+    pub fn refer(&mut self, entity: Entity) -> String {
+        let reference = entity.reference();
+        self.referenced.insert(entity);
+        reference
+    }
+
+    /// Defines the first entity of a numbered kind (e.g. `Entity::Cursor`) that isn't defined
+    /// yet.
+    pub fn allocate(&mut self, kind: impl Fn(usize) -> Entity) -> Entity {
+        let entity = (0..)
+            .map(&kind)
+            .find(|entity| !self.defined.contains(entity))
+            .unwrap();
+        self.defined.insert(entity.clone());
+        entity
+    }
+
+    /// Records every entity defined or referred to anywhere in the given value.
+    pub fn record(&mut self, value: &Bson) {
+        match value {
+            Bson::String(s) => {
+                for captures in PLACEHOLDER.captures_iter(s) {
+                    if let Some(entity) = Entity::from_key(&captures[1]) {
+                        match &captures[2] {
+                            "DEFINITION" => self.defined.insert(entity),
+                            _ => self.referenced.insert(entity),
+                        };
+                    }
+                }
+            }
+            Bson::Document(doc) => doc.values().for_each(|value| self.record(value)),
+            Bson::Array(values) => values.iter().for_each(|value| self.record(value)),
+            _ => {}
+        }
+    }
+
+    /// Adds everything the other registry tracks to this one.
+    pub fn merge(&mut self, other: &EntityRegistry) {
+        self.defined.extend(other.defined.iter().cloned());
+        self.referenced.extend(other.referenced.iter().cloned());
+        self.values
+            .extend(other.values.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    pub fn defined(&self) -> &BTreeSet<Entity> {
+        &self.defined
+    }
+
+    pub fn referenced(&self) -> &BTreeSet<Entity> {
+        &self.referenced
+    }
+
+    /// The names of the sessions that are either defined or referred to.
+    pub fn sessions(&self) -> impl Iterator<Item = &str> {
+        self.used().into_iter().filter_map(|entity| match entity {
+            Entity::Session(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The entities that are either defined or referred to.
+    pub fn used(&self) -> BTreeSet<&Entity> {
+        self.defined.union(&self.referenced).collect()
+    }
+
+    /// Replaces the placeholders of the entities this registry tracks in a serialized file with
+    /// anchors and aliases, naming the entities according to `naming`. Any other literal
+    /// `replacements` are made in the same pass over the file.
+    pub(crate) fn emit_anchors(
+        &self,
+        yaml: &str,
+        naming: EntityNaming,
+        replacements: &[(&str, &str)],
+    ) -> String {
+        let substitutions = self.substitutions(naming);
+        let (patterns, replacements): (Vec<&str>, Vec<&str>) = substitutions
+            .iter()
+            .map(|(placeholder, anchor)| (placeholder.as_str(), anchor.as_str()))
+            .chain(replacements.iter().copied())
+            .unzip();
+        AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns)
+            .expect("placeholders are valid patterns")
+            .replace_all(yaml, &replacements)
+    }
+
+    /// The anchors and aliases that `emit_anchors` substitutes for each placeholder of the
+    /// entities this registry tracks.
+    pub(crate) fn substitutions(&self, naming: EntityNaming) -> BTreeMap<String, String> {
+        let definitions = self
+            .defined
+            .iter()
+            .map(|entity| (entity.definition(), self.anchor(entity, true, naming)));
+        let references = self
+            .referenced
+            .iter()
+            .map(|entity| (entity.reference(), self.anchor(entity, false, naming)));
+        definitions.chain(references).collect()
+    }
+
+    /// The value that each placeholder of the entities this registry tracks reads back as once
+    /// `emit_anchors` has replaced it and aliases have been resolved.
+    pub(crate) fn resolved_values(&self, naming: EntityNaming) -> BTreeMap<String, String> {
+        let value = |entity: &Entity| {
+            self.values
+                .get(entity)
+                .cloned()
+                .unwrap_or_else(|| entity.id(naming))
+        };
+        let definitions = self
+            .defined
+            .iter()
+            .map(|entity| (entity.definition(), value(entity)));
+        let references = self
+            .referenced
+            .iter()
+            .map(|entity| (entity.reference(), value(entity)));
+        definitions.chain(references).collect()
+    }
+
+    /// Checks that, in the serialized file, every reference to an entity comes after its
+    /// definition and every anchored definition is referred to, since a mistake in tracking the
+    /// entities would otherwise produce a file with dangling aliases or unused anchors.
+    pub(crate) fn check_anchors(
+        &self,
+        yaml: &str,
+        naming: EntityNaming,
+    ) -> Result<(), ConversionError> {
+        let mut defined = BTreeSet::new();
+        let mut referenced = BTreeSet::new();
+        for captures in PLACEHOLDER.captures_iter(yaml) {
+            let entity = match Entity::from_key(&captures[1]) {
+                Some(entity) => entity,
+                None => continue,
+            };
+            if &captures[2] == "DEFINITION" {
+                defined.insert(entity);
+            } else if defined.contains(&entity) {
+                referenced.insert(entity);
+            } else {
+                return Err(ConversionError::DanglingAlias(entity.id(naming)));
+            }
+        }
+        match self.referenced.difference(&referenced).next() {
+            Some(entity) => Err(ConversionError::UnusedAnchor(entity.id(naming))),
+            None => Ok(()),
+        }
+    }
+
+    /// Only entities that are referred to are anchored, so that files don't have unused anchors.
+    fn anchor(&self, entity: &Entity, definition: bool, naming: EntityNaming) -> String {
+        let id = entity.id(naming);
+        if definition {
+            let value = self.values.get(entity).unwrap_or(&id);
+            if self.referenced.contains(entity) {
+                format!("&{} {}", id, scalars::scalar(value))
+            } else {
+                scalars::scalar(value)
+            }
+        } else {
+            format!("*{}", id)
+        }
+    }
+}
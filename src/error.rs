@@ -0,0 +1,155 @@
+use std::fmt;
+
+/// An error that prevented a legacy test file from being converted.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ConversionError {
+    #[error("failed to parse test file at {path}: {source}")]
+    Parse {
+        /// The path to the value that failed to parse, e.g. `tests[3].operations[1].arguments`.
+        path: String,
+        source: serde_yaml::Error,
+    },
+
+    #[error("failed to serialize converted test file: {0}")]
+    Serialize(#[from] bson::ser::Error),
+
+    #[error("failed to write converted test file: {0}")]
+    Emit(#[source] serde_yaml::Error),
+
+    #[error("converted test file isn't valid YAML: {0}")]
+    Malformed(#[source] serde_yaml::Error),
+
+    #[error("converted test file doesn't read back as what was converted: {0}")]
+    RoundTrip(Box<crate::diff::Difference>),
+
+    #[error("converted test file refers to {0} before its anchor, or without one")]
+    DanglingAlias(String),
+
+    #[error("converted test file anchors {0} but never refers to it")]
+    UnusedAnchor(String),
+
+    #[error("can't rename entity {from} to {to}, which the file already has")]
+    EntityIdConflict { from: String, to: String },
+
+    #[error("can't merge {file} with the other files, which differ from it: {difference}")]
+    Unmergeable {
+        file: String,
+        difference: Box<crate::diff::Difference>,
+    },
+
+    #[error("converted test file would be rejected by a unified test runner at {path}: {source}")]
+    Invalid {
+        /// The path to the rejected value, e.g. `tests[0].operations[2].expectError`.
+        path: String,
+        source: serde_yaml::Error,
+    },
+
+    #[cfg(feature = "json-schema")]
+    #[error(
+        "converted test file doesn't match the unified test format schema: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    SchemaViolations(Vec<crate::schema::SchemaViolation>),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid nested operation: {0}")]
+    InvalidOperation(#[from] bson::de::Error),
+
+    #[error("unsupported operation {name}: {reason}")]
+    UnsupportedOperation { name: String, reason: String },
+
+    #[error("unsupported initial data: {0}")]
+    UnsupportedData(String),
+
+    #[error("unrecognized event: {0}")]
+    UnrecognizedEvent(String),
+
+    #[error("thread {0} was never started")]
+    UnknownThread(String),
+
+    #[error("{operation} is missing required argument {argument}")]
+    MissingArgument {
+        operation: String,
+        argument: &'static str,
+    },
+
+    #[error("{operation} argument {argument} must be a {expected}")]
+    InvalidArgument {
+        operation: String,
+        argument: &'static str,
+        expected: &'static str,
+    },
+
+    #[error("{0} can't be expressed in the legacy format")]
+    Inexpressible(String),
+
+    #[error("legacy test file contains the converter's placeholder text {0}")]
+    PlaceholderCollision(String),
+
+    #[error("{location}{error}")]
+    Context {
+        location: Location,
+        error: Box<ConversionError>,
+    },
+}
+
+/// Where in a legacy test file an error or warning occurred.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    pub file: Option<String>,
+    pub test: Option<String>,
+    pub operation: Option<usize>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}: ", file)?;
+        }
+        if let Some(ref test) = self.test {
+            write!(f, "test \"{}\": ", test)?;
+        }
+        if let Some(operation) = self.operation {
+            write!(f, "operation {}: ", operation)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_yaml::Error>> for ConversionError {
+    fn from(error: serde_path_to_error::Error<serde_yaml::Error>) -> Self {
+        Self::Parse {
+            path: error.path().to_string(),
+            source: error.into_inner(),
+        }
+    }
+}
+
+impl ConversionError {
+    pub(crate) fn in_operation(self, test: impl Into<String>, operation: usize) -> Self {
+        self.located(|location| {
+            location.test = Some(test.into());
+            location.operation = Some(operation);
+        })
+    }
+
+    pub(crate) fn in_test(self, test: impl Into<String>) -> Self {
+        self.located(|location| location.test = Some(test.into()))
+    }
+
+    pub(crate) fn in_file(self, file: impl Into<String>) -> Self {
+        self.located(|location| location.file = Some(file.into()))
+    }
+
+    fn located(self, update: impl FnOnce(&mut Location)) -> Self {
+        let (mut location, error) = match self {
+            Self::Context { location, error } => (location, error),
+            error => (Location::default(), Box::new(error)),
+        };
+        update(&mut location);
+        Self::Context { location, error }
+    }
+}
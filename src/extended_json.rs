@@ -0,0 +1,76 @@
+//! Preservation of the BSON types that YAML can only express as extended JSON, like 64-bit
+//! integers and binary data, through parsing and emitting test files.
+
+use bson::{doc, Bson};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::ConversionError;
+
+/// Parses a YAML test file. The YAML parser hands every negative integer to BSON as a 64-bit
+/// one, which would make `-1` indistinguishable from `{ $numberLong: "-1" }`, so negative
+/// integers that fit in 32 bits are parsed as 32-bit integers, like positive ones are.
+pub(crate) fn from_str<T: DeserializeOwned>(
+    input: &str,
+) -> Result<T, serde_path_to_error::Error<serde_yaml::Error>> {
+    let mut value: Value =
+        serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(input))?;
+    narrow_negative_integers(&mut value);
+    serde_path_to_error::deserialize(value).map_err(|narrowed_error| {
+        // errors from a parsed value don't say which line they're on, so if the file doesn't
+        // parse as it is either, that error is returned instead. Otherwise the narrowing is what
+        // failed, e.g. for a field that isn't BSON, and the file isn't parsed without it, since
+        // that would silently turn every negative integer in it into a 64-bit one.
+        match serde_path_to_error::deserialize::<_, T>(serde_yaml::Deserializer::from_str(input)) {
+            Err(error) => error,
+            Ok(_) => narrowed_error,
+        }
+    })
+}
+
+fn narrow_negative_integers(value: &mut Value) {
+    match value {
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64().filter(|i| *i < 0 && *i >= i32::MIN as i64) {
+                let mut number_int = Mapping::new();
+                number_int.insert("$numberInt".into(), i.to_string().into());
+                *value = Value::Mapping(number_int);
+            }
+        }
+        Value::Mapping(mapping) => mapping
+            .iter_mut()
+            .for_each(|(_, value)| narrow_negative_integers(value)),
+        Value::Sequence(values) => values.iter_mut().for_each(narrow_negative_integers),
+        _ => {}
+    }
+}
+
+/// Serializes a test file into a YAML value, writing the values whose type plain YAML would
+/// lose as extended JSON.
+pub(crate) fn to_value<T: Serialize>(file: &T) -> Result<Value, ConversionError> {
+    let mut file = bson::to_bson(file)?;
+    preserve_types(&mut file);
+    serde_yaml::to_value(&file).map_err(ConversionError::Emit)
+}
+
+/// Rewrites 64-bit integers, which would otherwise be emitted as plain integers, and generic
+/// binary data, which would otherwise be emitted as a sequence of bytes, as extended JSON.
+/// The other types BSON has but YAML doesn't are already serialized as extended JSON.
+fn preserve_types(value: &mut Bson) {
+    match value {
+        Bson::Int64(i) => *value = Bson::Document(doc! { "$numberLong": i.to_string() }),
+        Bson::Binary(_) => {
+            let extended = value.clone().into_relaxed_extjson();
+            let binary = &extended["$binary"];
+            *value = Bson::Document(doc! {
+                "$binary": {
+                    "base64": binary["base64"].as_str().unwrap_or_default(),
+                    "subType": binary["subType"].as_str().unwrap_or_default(),
+                }
+            });
+        }
+        Bson::Document(doc) => doc.iter_mut().for_each(|(_, value)| preserve_types(value)),
+        Bson::Array(values) => values.iter_mut().for_each(preserve_types),
+        _ => {}
+    }
+}
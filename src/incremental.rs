@@ -0,0 +1,167 @@
+//! Tracking of what previous conversions have already done, so re-running the converter only
+//! converts the files that changed since.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{convert_str, ConversionError, ConvertOptions};
+
+/// The name of the state file that batch conversions keep next to their output directory.
+pub static STATE_FILE_NAME: &str = ".v2-to-unified-state.json";
+
+/// The source files a batch conversion converted, and what with.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionState {
+    /// The fingerprint of the converter version and options the files were converted with.
+    fingerprint: String,
+
+    /// The hash of each converted source file, keyed by file name.
+    files: BTreeMap<String, String>,
+}
+
+impl ConversionState {
+    /// Loads the state of a previous conversion with the given options. Files are only
+    /// considered converted if they were converted by the same version of the converter with
+    /// the same options, and if there's no usable state everything is reconverted.
+    pub fn load(path: &Path, options: &ConvertOptions) -> Self {
+        let fingerprint = fingerprint(options);
+        let previous = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|state| serde_json::from_str::<Self>(&state).ok());
+        match previous {
+            Some(state) if state.fingerprint == fingerprint => state,
+            _ => Self {
+                fingerprint,
+                files: BTreeMap::new(),
+            },
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let state = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+        std::fs::write(path, state)
+    }
+
+    /// Whether the file with the given name and contents was converted by a previous run.
+    pub fn is_current(&self, file_name: &str, contents: &str) -> bool {
+        self.files.get(file_name) == Some(&content_hash(contents))
+    }
+
+    /// Records that the file with the given name and contents was converted.
+    pub fn record(&mut self, file_name: &str, contents: &str) {
+        self.files
+            .insert(file_name.to_string(), content_hash(contents));
+    }
+
+    /// Forgets a file, e.g. because it failed to convert, so the next run tries it again.
+    pub fn forget(&mut self, file_name: &str) {
+        self.files.remove(file_name);
+    }
+}
+
+/// Converted files, keyed by their source, the options they were converted with, and the
+/// converter version, so that modes which only compare output can skip converting.
+#[derive(Clone, Debug)]
+pub struct ConversionCache {
+    dir: PathBuf,
+}
+
+impl ConversionCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The user's cache directory for the converter, e.g. `~/.cache/v2-to-unified`.
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir)
+            .join("v2-to-unified")
+    }
+
+    /// Converts a legacy test file like [`convert_str`], reusing the output of a previous
+    /// conversion of the same file with the same options if there is one. The cache is best
+    /// effort, so failing to read or write it just means converting.
+    pub fn convert_str(
+        &self,
+        input: &str,
+        options: &ConvertOptions,
+    ) -> Result<String, ConversionError> {
+        let mut hasher = Sha256::new();
+        hasher.update(fingerprint(options));
+        hasher.update(format!("{:?}", options.description));
+        hasher.update(input);
+        let path = self.dir.join(format!("{:x}.yml", hasher.finalize()));
+
+        if let Ok(output) = std::fs::read_to_string(&path) {
+            return Ok(output);
+        }
+        let (output, _) = convert_str(input, options)?;
+        if std::fs::create_dir_all(&self.dir).is_ok() {
+            let _ = std::fs::write(&path, &output);
+        }
+        Ok(output)
+    }
+}
+
+/// A hex-encoded SHA-256 hash of the given contents.
+pub fn content_hash(contents: &str) -> String {
+    format!("{:x}", Sha256::digest(contents.as_bytes()))
+}
+
+/// A hash identifying the converter version and the options that affect its output, other than
+/// the per-file description.
+pub fn fingerprint(options: &ConvertOptions) -> String {
+    // destructured so that new options can't be forgotten here.
+    let ConvertOptions {
+        schema_version,
+        default_database_name,
+        ignore_command_monitoring_events,
+        ignored_commands,
+        todo_comments,
+        hoist_entities,
+        alias_initial_data,
+        scope_setup_client,
+        normalize_topologies,
+        observe_events,
+        entity_naming,
+        strict,
+        description: _,
+        operations,
+    } = options;
+    // nested since tuples are only `Debug` up to twelve elements.
+    let options = format!(
+        "{:?}",
+        (
+            (
+                schema_version,
+                default_database_name,
+                ignore_command_monitoring_events,
+                ignored_commands,
+                todo_comments,
+            ),
+            (
+                hoist_entities,
+                alias_initial_data,
+                scope_setup_client,
+                normalize_topologies,
+                observe_events,
+                entity_naming,
+                strict,
+                operations,
+            ),
+        )
+    );
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(options);
+    format!("{:x}", hasher.finalize())
+}
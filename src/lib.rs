@@ -0,0 +1,43 @@
+//! Converts legacy v2 MongoDB spec tests into the unified test format.
+
+pub mod batch;
+pub mod convert;
+pub mod crud_v2;
+pub mod diff;
+pub mod downgrade;
+pub mod entities;
+mod error;
+mod extended_json;
+pub mod incremental;
+pub mod lint;
+mod matchers;
+pub mod merge;
+pub mod naming;
+mod normalize;
+pub mod operations;
+#[cfg(feature = "python")]
+mod python;
+pub mod report;
+mod scalars;
+#[cfg(feature = "json-schema")]
+pub mod schema;
+pub mod specifications;
+pub mod split;
+pub mod stats;
+pub mod summary;
+pub mod unified;
+#[cfg(feature = "validate")]
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use convert::{convert, convert_reader, convert_str, ConvertOptions, ConvertOptionsBuilder};
+pub use error::{ConversionError, Location};
+pub use report::{ConversionReport, Warning, WarningKind};
+
+/// The unified test format schema version that converted files declare.
+static SCHEMA_VERSION: &str = "1.10";
+
+fn unknown_operation_placeholder(name: &str) -> String {
+    format!("xUNKNOWN_OPERATION_PLACEHOLDER_{}", name)
+}
@@ -0,0 +1,170 @@
+//! Checks for problems in legacy test files that will make their conversion fail or come out
+//! wrong, so they can be fixed at the source first.
+
+use std::fmt;
+
+use crate::{
+    crud_v2::{self, CmapEvent, OperationResult, SdamEvent, TestData},
+    ConversionError,
+    Location,
+};
+
+/// How much a finding affects the conversion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The converted file may not test what the legacy one did.
+    Warning,
+
+    /// The file can't be converted.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a legacy test file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}: {}", self.location, self.severity, self.message)
+    }
+}
+
+/// Lints the YAML contents of the legacy test file with the given name.
+pub fn lint_str(file_name: &str, input: &str) -> Vec<Finding> {
+    let file = serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(input));
+    let mut findings = match file {
+        Ok(file) => lint(&file),
+        Err(e) => vec![Finding {
+            severity: Severity::Error,
+            location: Location::default(),
+            message: ConversionError::from(e).to_string(),
+        }],
+    };
+    for finding in findings.iter_mut() {
+        finding.location.file = Some(file_name.to_string());
+    }
+    findings
+}
+
+/// Lints a parsed legacy test file.
+pub fn lint(file: &crud_v2::TestFile) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut find = |severity, location: Location, message: String| {
+        findings.push(Finding {
+            severity,
+            location,
+            message,
+        })
+    };
+
+    if let TestData::Many(ref collections) = file.data {
+        let outcome_collections: Vec<_> = file
+            .tests
+            .iter()
+            .filter_map(|test| test.outcome.as_ref()?.collection.name.as_deref())
+            .collect();
+        for name in collections.keys() {
+            if *name != file.collection_name && !outcome_collections.contains(&name.as_str()) {
+                find(
+                    Severity::Warning,
+                    Location::default(),
+                    format!(
+                        "data for collection {}, which no test uses or checks the outcome of",
+                        name
+                    ),
+                );
+            }
+        }
+    }
+
+    for test in file.tests.iter() {
+        let test_location = Location {
+            test: Some(test.description.clone()),
+            ..Default::default()
+        };
+        if let Some(ref reason) = test.skip_reason {
+            if reason.trim().is_empty() {
+                find(
+                    Severity::Warning,
+                    test_location.clone(),
+                    "skipReason doesn't explain why the test is skipped".to_string(),
+                );
+            }
+        }
+
+        for (i, op) in test.operations.iter().enumerate() {
+            let location = Location {
+                operation: Some(i),
+                ..test_location.clone()
+            };
+            let command = op.arguments.as_ref().and_then(|a| a.get_document("command").ok());
+            if matches!(op.name.as_str(), "runCommand" | "runAdminCommand")
+                && op.command_name.is_none()
+            {
+                match command.and_then(|command| command.keys().next()) {
+                    Some(first_key) => find(
+                        Severity::Warning,
+                        location.clone(),
+                        format!(
+                            "{} has no command_name, so {} will be assumed",
+                            op.name, first_key
+                        ),
+                    ),
+                    None => find(
+                        Severity::Error,
+                        location.clone(),
+                        format!("{} has neither a command_name nor a command", op.name),
+                    ),
+                }
+            }
+
+            if matches!(op.name.as_str(), "waitForEvent" | "assertEventCount") {
+                match op.arguments.as_ref().and_then(|a| a.get_str("event").ok()) {
+                    Some(event)
+                        if CmapEvent::unified_name_of(event).is_none()
+                            && SdamEvent::unified_name_of(event).is_none() =>
+                    {
+                        find(
+                            Severity::Error,
+                            location.clone(),
+                            format!("{} waits for unknown event {}", op.name, event),
+                        )
+                    }
+                    Some(_) => {}
+                    None => find(
+                        Severity::Error,
+                        location.clone(),
+                        format!("{} doesn't name an event", op.name),
+                    ),
+                }
+            }
+
+            if op.error == Some(false) && matches!(op.result, Some(OperationResult::Error(_))) {
+                find(
+                    Severity::Warning,
+                    location,
+                    format!(
+                        "{} says it doesn't error but has an error result, it will be converted \
+                         to expect an error",
+                        op.name
+                    ),
+                );
+            }
+        }
+    }
+
+    findings
+}
@@ -1,744 +1,639 @@
-use anyhow::Result;
-use regex::Regex;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use v2_to_unified::{
+    batch::{BatchConfig, OutputFormat},
+    convert_str,
+    diff::diff_str,
+    downgrade::downgrade_str,
+    incremental::{ConversionCache, ConversionState, STATE_FILE_NAME},
+    entities::EntityNaming,
+    lint::{lint_str, Severity},
+    merge::{merge_files, mergeable_groups},
+    naming,
+    split::split_tests,
+    specifications::SPEC_FAMILIES,
+    stats::CorpusStats,
+    summary::MigrationSummary,
+    ConvertOptions,
+};
+
+#[derive(Parser)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+
+    #[clap(flatten)]
+    options: ConvertOptions,
+
+    /// Convert every file, even ones that haven't changed since the last conversion.
+    #[clap(long)]
+    force: bool,
+
+    /// Check that the converted files are up to date instead of writing them, exiting with a
+    /// non-zero status if any aren't.
+    #[clap(long)]
+    check: bool,
+
+    /// Only convert the legacy files that git says have changed since the given ref, including
+    /// uncommitted changes and new untracked files.
+    #[clap(long, value_name = "GIT_REF")]
+    since: Option<String>,
+
+    /// Write a Markdown summary of the files this run converted, what the conversion dropped or
+    /// approximated, and which files couldn't be converted, e.g. for a pull request description.
+    #[clap(long, value_name = "PATH")]
+    summary: Option<PathBuf>,
+
+    /// Write a JSON list of the unified file and test that each legacy test this run converted
+    /// became, e.g. for updating skip lists that refer to tests by their legacy descriptions.
+    #[clap(long, value_name = "PATH")]
+    mapping: Option<PathBuf>,
+
+    /// Write a JSON object of the unified test runner features each file this run converted
+    /// uses, keyed by the converted file: its `testRunner` operations, special operators,
+    /// events, and the schema version they need.
+    #[clap(long, value_name = "PATH")]
+    capabilities: Option<PathBuf>,
+
+    /// Rename the entities in the unified files of each output directory that don't follow
+    /// --entity-naming, e.g. files converted with another naming or written by hand, so that
+    /// every file in the directory names its entities the same way.
+    #[clap(long, conflicts_with = "check")]
+    rename_entities: bool,
+
+    /// Write one unified file per legacy test, named after the legacy file and the test's
+    /// description, e.g. `insert-one-write-error.yml`, each with its own entities and initial
+    /// data.
+    #[clap(long)]
+    split_tests: bool,
+
+    /// Merge legacy files that only differ in their tests, e.g. ones with the same `runOn`
+    /// requirements and data, into one unified file named after the first of them, with their
+    /// shared entities and initial data written once.
+    #[clap(long, conflicts_with = "split-tests")]
+    merge: bool,
+
+    /// Don't print warnings or errors to stderr.
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Only print one stable line per legacy file to stdout, for scripts: `OK <in> <out>`,
+    /// `SKIP <in> <reason>`, or `FAIL <in> <error>`.
+    #[clap(long)]
+    porcelain: bool,
+
+    /// Log how long each phase of each file's conversion takes. Pass twice to time each
+    /// operation too.
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
 
-use std::{fs::File, io::Write, path::PathBuf};
+}
 
-use bson::{doc, Document};
-use serde::Deserialize;
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a unified test file back into the legacy format, where it can be expressed.
+    Downgrade {
+        input: PathBuf,
 
-use crate::{
-    crud_v2::TestData,
-    unified::{
-        ClientEntity, CollectionEntity, CreateEntity, DatabaseEntity, ExpectEvent, InitialData,
-        Test,
+        /// Where to write the legacy file. Defaults to stdout.
+        output: Option<PathBuf>,
     },
-};
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase", deny_unknown_fields)]
-pub(crate) enum Serverless {
-    Require,
-    Forbid,
-    Allow,
+    /// Convert a legacy test file and compare the result to an existing unified file, ignoring
+    /// key order and anchor names. Exits with a non-zero status if they differ.
+    Diff { v2_file: PathBuf, unified_file: PathBuf },
+
+    /// Report which operations, events, and other constructs the legacy files in a directory
+    /// use, and which of them the converter doesn't support yet.
+    Stats { dir: PathBuf },
+
+    /// Check legacy files, or the legacy files in directories, for problems that will affect
+    /// their conversion. Exits with a non-zero status if any would fail to convert.
+    Lint { paths: Vec<PathBuf> },
+
+    /// Convert the legacy files in a directory, writing the converted files to another.
+    Convert {
+        /// The directory of legacy files, e.g. a spec family's `tests/legacy`.
+        input: PathBuf,
+
+        /// The directory to write the converted files to, e.g. the family's `tests/unified`.
+        output: PathBuf,
+    },
+
+    /// Convert the legacy tests of every spec family in a checkout of the specifications
+    /// repository, writing each family's converted files to its `tests/unified` directory.
+    ConvertRepo { root: PathBuf },
+
+    /// Convert each of the directories of legacy tests listed in a YAML or JSON config file,
+    /// with the output directory, format, and options it gives for each. The options given on
+    /// the command line aren't used.
+    ConvertBatch { config: PathBuf },
 }
 
-mod crud_v2 {
-    use crate::unified::POOL_READY;
-
-    use super::Serverless;
-    use bson::{from_document, Bson, Document};
-    use serde::{Deserialize, Deserializer};
-    use std::collections::{HashMap, HashSet};
-
-    #[derive(Deserialize)]
-    #[serde(deny_unknown_fields)]
-    pub(crate) struct TestFile {
-        #[serde(rename = "runOn")]
-        pub(crate) run_on: Option<Vec<RunOn>>,
-        pub(crate) database_name: Option<String>,
-        pub(crate) collection_name: String,
-        pub(crate) bucket_name: Option<String>,
-        pub(crate) data: TestData,
-        pub(crate) tests: Vec<Test>,
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if cli.verbose > 0 {
+        tracing_subscriber::fmt()
+            .with_max_level(if cli.verbose > 1 {
+                Level::TRACE
+            } else {
+                Level::DEBUG
+            })
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(std::io::stderr)
+            .init();
+    }
+    let batch = Batch {
+        force: cli.force,
+        check: cli.check,
+        since: cli.since,
+        summary: cli.summary,
+        mapping: cli.mapping,
+        capabilities: cli.capabilities,
+        rename_entities: cli.rename_entities,
+        split_tests: cli.split_tests,
+        merge: cli.merge,
+        quiet: cli.quiet,
+        porcelain: cli.porcelain,
+    };
+    match cli.command {
+        Command::Downgrade { input, output } => downgrade(input, output),
+        Command::Diff {
+            v2_file,
+            unified_file,
+        } => diff(v2_file, unified_file, cli.options),
+        Command::Stats { dir } => stats(dir),
+        Command::Lint { paths } => lint(paths),
+        Command::Convert { input, output } => convert(input, output, cli.options, batch),
+        Command::ConvertRepo { root } => convert_repo(root, cli.options, batch),
+        Command::ConvertBatch { config } => convert_batch(config, batch),
     }
+}
 
-    #[derive(Debug, Deserialize)]
-    #[serde(rename_all = "camelCase", deny_unknown_fields)]
-    pub(crate) struct RunOn {
-        pub(crate) min_server_version: Option<String>,
-        pub(crate) max_server_version: Option<String>,
-        pub(crate) topology: Option<Vec<String>>,
-        pub(crate) serverless: Option<Serverless>,
-        pub(crate) auth_enabled: Option<bool>,
+fn downgrade(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let legacy = downgrade_str(&std::fs::read_to_string(input)?)?;
+    match output {
+        Some(output) => std::fs::write(output, legacy)?,
+        None => print!("{}", legacy),
     }
+    Ok(())
+}
 
-    #[derive(Debug, Deserialize)]
-    #[serde(untagged)]
-    pub(crate) enum TestData {
-        Single(Vec<Document>),
-        Many(HashMap<String, Vec<Document>>),
+fn diff(v2_file: PathBuf, unified_file: PathBuf, mut options: ConvertOptions) -> Result<()> {
+    if options.description.is_none() {
+        options.description = v2_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
     }
+    let cache = ConversionCache::new(ConversionCache::default_dir());
+    let converted = cache.convert_str(&std::fs::read_to_string(&v2_file)?, &options)?;
+    let differences = diff_str(&std::fs::read_to_string(&unified_file)?, &converted)?;
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    if !differences.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub(crate) struct Test {
-        pub(crate) description: String,
-        pub(crate) skip_reason: Option<String>,
-        pub(crate) use_multiple_mongoses: Option<bool>,
-        #[serde(default, rename = "clientOptions")]
-        pub(crate) client_uri: Option<Document>,
-        pub(crate) fail_point: Option<Document>,
-        pub(crate) session_options: Option<HashMap<String, Document>>,
-        pub(crate) operations: Vec<Operation>,
-        #[serde(default, deserialize_with = "deserialize_command_started_events")]
-        pub(crate) expectations: Option<Vec<CommandStartedEvent>>,
-        pub(crate) outcome: Option<Outcome>,
+fn stats(dir: PathBuf) -> Result<()> {
+    let mut stats = CorpusStats::default();
+    for path in yaml_files(&dir)? {
+        let file_name = path.file_stem().unwrap().to_string_lossy().to_string();
+        stats.add_str(&file_name, &std::fs::read_to_string(&path)?);
     }
+    print!("{}", stats);
+    Ok(())
+}
 
-    impl Test {
-        pub(crate) fn observed_events(&self) -> HashSet<&'static str> {
-            let mut observe_events = HashSet::new();
-            if self.expectations.is_some() {
-                observe_events.insert("commandStartedEvent");
+fn lint(paths: Vec<PathBuf>) -> Result<()> {
+    let mut failed = false;
+    for path in paths {
+        let files = if path.is_dir() {
+            yaml_files(&path)?
+        } else {
+            vec![path]
+        };
+        for file in files {
+            let file_name = file.file_name().unwrap().to_string_lossy().to_string();
+            for finding in lint_str(&file_name, &std::fs::read_to_string(&file)?) {
+                failed |= finding.severity == Severity::Error;
+                println!("{}", finding);
             }
-            observe_events
         }
     }
-
-    #[derive(Debug, Deserialize)]
-    #[serde(deny_unknown_fields)]
-    pub struct CommandStartedEvent {
-        pub command_name: Option<String>,
-        pub database_name: Option<String>,
-        pub command: Document,
+    if failed {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    #[derive(Debug, Deserialize)]
-    pub(crate) struct Outcome {
-        pub(crate) collection: CollectionOutcome,
+/// The YAML files directly inside a directory, in name order.
+fn yaml_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if matches!(path.extension().and_then(|e| e.to_str()), Some("yml" | "yaml")) {
+            paths.push(path);
+        }
     }
+    paths.sort();
+    Ok(paths)
+}
 
-    #[derive(Debug, Deserialize)]
-    pub(crate) struct CollectionOutcome {
-        pub(crate) name: Option<String>,
-        pub(crate) data: Vec<Document>,
-    }
+/// How a batch of files should be converted.
+struct Batch {
+    force: bool,
+    check: bool,
+    since: Option<String>,
+    summary: Option<PathBuf>,
+    mapping: Option<PathBuf>,
+    capabilities: Option<PathBuf>,
+    rename_entities: bool,
+    split_tests: bool,
+    merge: bool,
+    quiet: bool,
+    porcelain: bool,
+}
 
-    #[derive(Debug, Deserialize, Clone)]
-    #[serde(rename_all = "camelCase", deny_unknown_fields)]
-    pub struct Operation {
-        pub name: String,
-        pub object: String,
-        // this field is required only for the runAdminCommand operation
-        #[serde(rename = "command_name")]
-        pub command_name: Option<String>,
-        pub arguments: Option<Document>,
-        pub error: Option<bool>,
-        pub result: Option<OperationResult>,
+impl Batch {
+    /// Prints progress for humans, unless the output is for scripts.
+    fn progress(&self, message: fmt::Arguments) {
+        if !self.porcelain {
+            println!("{}", message);
+        }
     }
 
-    #[derive(Debug, Deserialize, Clone)]
-    #[serde(untagged)]
-    pub enum OperationResult {
-        Error(OperationError),
-        Success(Bson),
+    /// Prints a warning or error, unless asked to be quiet.
+    fn complain(&self, message: fmt::Arguments) {
+        if !self.quiet {
+            eprintln!("{}", message);
+        }
     }
 
-    #[derive(Clone, Debug, Deserialize)]
-    #[serde(rename_all = "camelCase", deny_unknown_fields)]
-    pub struct OperationError {
-        pub error_contains: Option<String>,
-        pub error_code_name: Option<String>,
-        pub error_code: Option<i32>,
-        pub error_labels_contain: Option<Vec<String>>,
-        pub error_labels_omit: Option<Vec<String>>,
+    /// Prints what happened to a legacy file, for scripts. `detail` is the output file or the
+    /// reason for skipping or failing, and is kept to the rest of the line.
+    fn record(&self, status: &str, input: &Path, detail: impl fmt::Display) {
+        if self.porcelain {
+            let detail = detail.to_string().replace('\n', " ");
+            println!("{} {} {}", status, input.display(), detail);
+        }
     }
 
-    fn deserialize_command_started_events<'de, D>(
-        deserializer: D,
-    ) -> std::result::Result<Option<Vec<CommandStartedEvent>>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let docs = Vec::<Document>::deserialize(deserializer)?;
-        Ok(Some(
-            docs.iter()
-                .map(|doc| {
-                    let event = doc.get_document("command_started_event").unwrap();
-                    from_document(event.clone()).unwrap()
-                })
-                .collect(),
-        ))
+    /// Writes the summary, test mapping, and capabilities of the batch, if they were asked for.
+    fn write_reports(&self, summary: &MigrationSummary) -> Result<()> {
+        if let Some(ref path) = self.summary {
+            std::fs::write(path, summary.to_string())?;
+        }
+        if let Some(ref path) = self.mapping {
+            std::fs::write(path, serde_json::to_string_pretty(&summary.tests)?)?;
+        }
+        if let Some(ref path) = self.capabilities {
+            std::fs::write(path, serde_json::to_string_pretty(&summary.capabilities)?)?;
+        }
+        Ok(())
     }
 }
 
-mod unified {
-    use std::collections::HashSet;
-
-    use bson::{doc, Bson, Document};
-    use serde::Serialize;
-    use serde_yaml::Value;
-
-    use crate::{ADMIN_DATABASE_DEREF_PLACEHOLDER, CLIENT_DEFINITION_PLACEHOLDER, CLIENT_DEREF_PLACEHOLDER, COLLECTION_DEFINITION_PLACEHOLDER, COLLECTION_DEREF_PLACEHOLDER, COLLECTION_NAME_DEFINITION_PLACEHOLDER, COLLECTION_NAME_DEREF_PLACEHOLDER, DATABASE_DEFINITION_PLACEHOLDER, DATABASE_DEREF_PLACEHOLDER, DATABASE_NAME_DEFINITION_PLACEHOLDER, DATABASE_NAME_DEREF_PLACEHOLDER, SETUP_CLIENT_DEREF_PLACEHOLDER, TOPOLOGY_DESCRIPTION_DEFINITION_PLACEHOLDER, TOPOLOGY_DESCRIPTION_DEREF_PLACEHOLDER, crud_v2::{self, OperationResult}, thread_definition_placeholder, thread_deref_placeholder};
-
-    pub static SERVER_DESCRIPTION_CHANGED: &'static str = "serverDescriptionChangedEvent";
-    pub static POOL_CLEARED: &'static str = "poolClearedEvent";
-    pub static POOL_READY: &'static str = "poolReadyEvent";
-
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    #[serde_with::skip_serializing_none]
-    pub struct TestFile {
-        pub description: String,
-        pub schema_version: String,
-        pub run_on_requirements: Option<Vec<RunOnRequirements>>,
-        pub create_entities: Option<Vec<CreateEntity>>,
-        pub initial_data: Option<Vec<InitialData>>,
-        pub tests: Vec<Test>,
-    }
-
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct InitialData {
-        pub collection_name: String,
-        pub database_name: String,
-        pub documents: Vec<Document>,
-    }
-
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub enum CreateEntity {
-        Client(ClientEntity),
-        Database(DatabaseEntity),
-        Collection(CollectionEntity),
-        Thread { id: String },
+fn convert_repo(root: PathBuf, options: ConvertOptions, batch: Batch) -> Result<()> {
+    let mut ok = true;
+    let mut summary = MigrationSummary::default();
+    for family in SPEC_FAMILIES {
+        let legacy = family.legacy_path(&root);
+        if !legacy.is_dir() {
+            batch.progress(format_args!("skipping {}: no legacy tests", family.name));
+            batch.record("SKIP", &legacy, "no legacy tests");
+            continue;
+        }
+        batch.progress(format_args!("{}:", family.name));
+        let unified = family.unified_path(&root);
+        std::fs::create_dir_all(&unified)?;
+        ok &= convert_dir(
+            &root,
+            &legacy,
+            &unified,
+            OutputFormat::Yaml,
+            &mut family.options(&options),
+            &batch,
+            &mut summary,
+        )?;
     }
-
-    #[serde_with::skip_serializing_none]
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct ClientEntity {
-        pub id: String,
-        pub observe_events: Option<HashSet<&'static str>>,
-        pub uri_options: Option<Document>,
+    batch.write_reports(&summary)?;
+    if !ok {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct DatabaseEntity {
-        pub id: String,
-        pub client: String,
-        pub database_name: String,
+fn convert_batch(config_path: PathBuf, batch: Batch) -> Result<()> {
+    let base = config_path.parent().unwrap_or_else(|| Path::new(""));
+    let config = BatchConfig::from_str(&std::fs::read_to_string(&config_path)?, base)
+        .with_context(|| format!("invalid batch config {}", config_path.display()))?;
+    let mut ok = true;
+    let mut summary = MigrationSummary::default();
+    for directory in config.directories.iter() {
+        let mut options = config
+            .options(directory)
+            .with_context(|| format!("invalid options for {}", directory.input.display()))?;
+        batch.progress(format_args!("{}:", directory.input.display()));
+        std::fs::create_dir_all(&directory.output)?;
+        ok &= convert_dir(
+            base,
+            &directory.input,
+            &directory.output,
+            directory.format,
+            &mut options,
+            &batch,
+            &mut summary,
+        )?;
     }
-
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct CollectionEntity {
-        pub id: String,
-        pub database: String,
-        pub collection_name: String,
+    batch.write_reports(&summary)?;
+    if !ok {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    #[serde_with::skip_serializing_none]
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct Test {
-        pub description: String,
-        pub run_on_requirements: Option<Vec<RunOnRequirements>>,
-        pub operations: Vec<Operation>,
-        pub expect_events: Option<Vec<ExpectEvents>>,
-        pub outcome: Option<Vec<InitialData>>,
+fn convert(
+    input: PathBuf,
+    output: PathBuf,
+    mut options: ConvertOptions,
+    batch: Batch,
+) -> Result<()> {
+    std::fs::create_dir_all(&output)?;
+    let root = input.parent().unwrap_or_else(|| Path::new(""));
+    let mut summary = MigrationSummary::default();
+    let ok = convert_dir(
+        root,
+        &input,
+        &output,
+        OutputFormat::Yaml,
+        &mut options,
+        &batch,
+        &mut summary,
+    )?;
+    batch.write_reports(&summary)?;
+    if !ok {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    impl Test {
-        pub(crate) fn from_crud_v2(old: crud_v2::Test, test_number: usize) -> Self {
-            let mut operations = Vec::new();
-            let observed_events = old.observed_events();
-            if let Some(fp) = old.fail_point {
-                operations.push(Operation {
-                    name: "failPoint".to_string(),
-                    object: "testRunner".to_string(),
-                    arguments: Some(doc! {
-                        "client": SETUP_CLIENT_DEREF_PLACEHOLDER,
-                        "failPoint": fp,
-                    }),
-                    save_result_as_entity: None,
-                    expect_result: None,
-                    expect_error: None,
-                });
-            }
-
-            let mut ents = vec![];
-            ents.push(CreateEntity::Client(ClientEntity {
-                id: CLIENT_DEFINITION_PLACEHOLDER.to_string(),
-                observe_events: Some(observed_events),
-                uri_options: old.client_uri.clone(),
-            }));
-            ents.push(CreateEntity::Database(DatabaseEntity {
-                id: DATABASE_DEFINITION_PLACEHOLDER.to_string(),
-                client: CLIENT_DEREF_PLACEHOLDER.to_string(),
-                database_name: DATABASE_NAME_DEREF_PLACEHOLDER.to_string(),
-            }));
-            ents.push(CreateEntity::Collection(CollectionEntity {
-                id: COLLECTION_DEFINITION_PLACEHOLDER.to_string(),
-                database: DATABASE_DEREF_PLACEHOLDER.to_string(),
-                collection_name: COLLECTION_NAME_DEREF_PLACEHOLDER.to_string(),
-            }));
-
-            if !ents.is_empty() {
-                operations.push(Operation {
-                    name: "createEntities".to_string(),
-                    object: "testRunner".to_string(),
-                    arguments: Some(doc! {
-                        "entities": bson::to_bson(&ents).unwrap(),
-                    }),
-                    expect_error: None,
-                    expect_result: None,
-                    save_result_as_entity: None,
-                });
-            }
+/// Converts the legacy files in one directory into another, returning whether they were all up
+/// to date in check mode. The conversion state is kept next to the
+/// output directory, and files are added to the summary by their path relative to `root`.
+fn convert_dir(
+    root: &Path,
+    integration: &Path,
+    unified: &Path,
+    format: OutputFormat,
+    options: &mut ConvertOptions,
+    batch: &Batch,
+    summary: &mut MigrationSummary,
+) -> Result<bool> {
+    let relative = |path: &Path| path.strip_prefix(root).unwrap_or(path).display().to_string();
+    let state_path = unified.parent().unwrap_or(unified).join(STATE_FILE_NAME);
+    let mut state = ConversionState::load(&state_path, options);
+    let cache = ConversionCache::new(ConversionCache::default_dir());
+    let mut out_of_date = false;
+    let changed = match batch.since {
+        Some(ref since) => Some(changed_files(integration, since)?),
+        None => None,
+    };
 
-            for old_op in old.operations {
-                operations.push(Operation::from_crud_v2(old_op, test_number));
+    // when merging, each group of mergeable files is converted in place of its first file, and
+    // the others are skipped.
+    let hoist_entities = options.hoist_entities;
+    let mut groups = BTreeMap::new();
+    let mut merged_into = BTreeMap::new();
+    if batch.merge {
+        let mut files = Vec::new();
+        for path in yaml_files(integration)? {
+            if path.extension() == Some("yml".as_ref()) {
+                let filename = path.file_name().unwrap().to_string_lossy().to_string();
+                files.push((filename, std::fs::read_to_string(&path)?));
             }
-
-            let expect_events = old.expectations.map(|old_events| {
-                vec![ExpectEvents {
-                    client: CLIENT_DEREF_PLACEHOLDER.to_string(),
-                    event_type: "command".to_string(),
-                    events: old_events
-                        .into_iter()
-                        .map(|event| ExpectEvent::CommandStartedEvent {
-                            command: event.command,
-                            command_name: event.command_name,
-                            database_name: Some(DATABASE_NAME_DEREF_PLACEHOLDER.to_string()),
-                        })
-                        .collect(),
-                }]
-            });
-
-            let outcome = old.outcome.map(|old_outcome| {
-                vec![InitialData {
-                    database_name: DATABASE_NAME_DEREF_PLACEHOLDER.to_string(),
-                    collection_name: COLLECTION_NAME_DEREF_PLACEHOLDER.to_string(),
-                    documents: old_outcome.collection.data,
-                }]
-            });
-
-            Self {
-                description: old.description,
-                run_on_requirements: None,
-                operations,
-                expect_events,
-                outcome,
+        }
+        for group in mergeable_groups(&files).into_iter().filter(|g| g.len() > 1) {
+            for filename in group[1..].iter() {
+                merged_into.insert(filename.clone(), group[0].clone());
             }
+            let group: Vec<_> = files
+                .iter()
+                .filter(|(filename, _)| group.contains(filename))
+                .cloned()
+                .collect();
+            groups.insert(group[0].0.clone(), group);
         }
     }
 
-    #[serde_with::skip_serializing_none]
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct RunOnRequirements {
-        min_server_version: Option<String>,
-        max_server_version: Option<String>,
-        topologies: Option<Vec<String>>,
-        auth: Option<bool>,
-    }
+    let paths = std::fs::read_dir(integration)?;
 
-    impl From<crud_v2::RunOn> for RunOnRequirements {
-        fn from(old: crud_v2::RunOn) -> Self {
-            Self {
-                min_server_version: old.min_server_version,
-                max_server_version: old.max_server_version,
-                topologies: old.topology,
-                auth: old.auth_enabled,
-            }
+    for path in paths {
+        let path = path?.path();
+        if path.extension() != Some("yml".as_ref()) {
+            continue;
+        }
+        let filename = path.file_name().unwrap().to_string_lossy().to_string();
+        if let Some(first) = merged_into.get(&filename) {
+            batch.record("SKIP", &path, format_args!("merged into {}", first));
+            continue;
+        }
+        let group = groups.get(&filename);
+        let unchanged = |changed: &BTreeSet<String>| match group {
+            Some(group) => !group.iter().any(|(filename, _)| changed.contains(filename)),
+            None => !changed.contains(&filename),
+        };
+        if matches!(changed, Some(ref changed) if unchanged(changed)) {
+            let since = batch.since.as_deref().unwrap_or_default();
+            batch.record("SKIP", &path, format_args!("unchanged since {}", since));
+            continue;
         }
-    }
-
-    #[serde_with::skip_serializing_none]
-    #[derive(Debug, Default, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct Operation {
-        name: String,
-        object: String,
-        arguments: Option<Document>,
-        save_result_as_entity: Option<String>,
-        expect_result: Option<Bson>,
-        expect_error: Option<ExpectError>,
-    }
 
-    impl Operation {
-        pub(crate) fn from_crud_v2(old_op: crud_v2::Operation, test_number: usize) -> Self {
-            let mut name = old_op.name;
-            let mut arguments = old_op.arguments;
-            let mut object = match old_op.object.as_str() {
-                "collection" => COLLECTION_DEREF_PLACEHOLDER.to_string(),
-                "database" => DATABASE_DEREF_PLACEHOLDER.to_string(),
-                _ => old_op.object,
-            };
-
-            match name.as_str() {
-                "waitForEvent" | "assertEventCount" => {
-                    let old_arguments = arguments.as_ref().unwrap();
-
-                    let event = match old_arguments.get_str("event").unwrap() {
-                        "ServerMarkedUnknownEvent" => doc! {
-                            SERVER_DESCRIPTION_CHANGED: {
-                                "newDescription": { "type": "Unknown" }
-                            }
-                        },
-                        "PoolClearedEvent" => doc! {
-                            POOL_CLEARED: { }
-                        },
-                        "PoolReadyEvent" => doc! { POOL_READY: { } },
-                        e => panic!("unrecognized event: {}", e),
-                    };
-
-                    arguments = doc! {
-                        "client": CLIENT_DEREF_PLACEHOLDER.to_string(),
-                        "event": event,
-                        "count": old_arguments.get("count").unwrap()
-                    }
-                    .into();
+        let input = match group {
+            Some(group) => match merge_files(group) {
+                Ok(merged) => merged,
+                Err(e) => {
+                    batch.complain(format_args!("error: {}: {}", filename, e));
+                    batch.record("FAIL", &path, &e);
+                    summary.add_skipped(relative(&path), &e);
+                    continue;
                 }
-                "recordPrimary" => {
-                    arguments = doc! {
-                        "client": CLIENT_DEREF_PLACEHOLDER.to_string(),
-                        "id": TOPOLOGY_DESCRIPTION_DEFINITION_PLACEHOLDER,
-                    }
-                    .into();
-                    name = "recordTopologyDescription".to_string();
+            },
+            None => std::fs::read_to_string(&path)?,
+        };
+        let stem = filename.strip_suffix(".yml").unwrap_or(&filename);
+        let files = if batch.split_tests {
+            match split_tests(&input) {
+                Ok(tests) => tests
+                    .into_iter()
+                    .map(|(slug, input)| (format!("{}-{}", stem, slug), input))
+                    .collect(),
+                Err(e) => {
+                    batch.complain(format_args!("error: {}: {}", filename, e));
+                    batch.record("FAIL", &path, &e);
+                    summary.add_skipped(relative(&path), &e);
+                    continue;
                 }
-                "waitForPrimaryChange" => {
-                    let mut new_arguments = doc! {
-                        "client": CLIENT_DEREF_PLACEHOLDER.to_string(),
-                        "priorTopologyDescription": TOPOLOGY_DESCRIPTION_DEREF_PLACEHOLDER,
-                    };
-                    if let Some(timeout) = arguments.as_ref().and_then(|a| a.get("timeoutMS")) {
-                        new_arguments.insert("timeoutMS", timeout);
+            }
+        } else {
+            vec![(stem.to_string(), input)]
+        };
+
+        // when splitting, each test is converted and tracked as if it were a legacy file of
+        // its own.
+        for (name, input) in files {
+            let filename = format!("{}.yml", name);
+            let out = unified.join(format!("{}.{}", name, format.extension()));
+            options.description = Some(name);
+            // merged files' tests share their entities, which are only written once if hoisted.
+            options.hoist_entities = hoist_entities || group.is_some();
+            if batch.check {
+                match cache
+                    .convert_str(&input, options)
+                    .and_then(|new| format.render(new))
+                {
+                    Ok(new)
+                        if std::fs::read_to_string(&out).ok().as_deref() == Some(new.as_str()) =>
+                    {
+                        batch.record("OK", &path, out.display());
                     }
-                    arguments = Some(new_arguments);
-                }
-                "runAdminCommand" => {
-                    arguments
-                        .as_mut()
-                        .unwrap()
-                        .insert("commandName", old_op.command_name.unwrap());
-                    object = ADMIN_DATABASE_DEREF_PLACEHOLDER.to_string();
-                    name = "runCommand".to_string();
-                }
-                "runCommand" => {
-                    arguments.as_mut().unwrap().insert("commandName", old_op.command_name.unwrap());
-                }
-                "startThread" => {
-                    let thread_name = arguments.as_ref().unwrap().get_str("name").unwrap();
-                    let thread_number = Operation::thread_number(thread_name);
-                    let thread_entity = CreateEntity::Thread {
-                        id: thread_definition_placeholder(thread_number),
-                    };
-                    name = "createEntities".to_string();
-                    object = "testRunner".to_string();
-                    arguments = doc! {
-                        "entities": [
-                            bson::to_bson(&thread_entity).unwrap()
-                        ]
+                    Ok(_) => {
+                        batch.progress(format_args!("out of date: {}", filename));
+                        batch.record("FAIL", &path, "out of date");
+                        out_of_date = true;
                     }
-                    .into();
-                }
-                "runOnThread" => {
-                    let old_arguments = arguments.as_ref().unwrap();
-                    let thread_name = old_arguments.get_str("name").unwrap();
-                    let thread_number = Operation::thread_number(thread_name);
-
-                    let old_operation: crud_v2::Operation =
-                        bson::from_bson(old_arguments.get("operation").unwrap().clone()).unwrap();
-                    let new_op = Operation::from_crud_v2(old_operation, test_number);
-
-                    arguments = doc! {
-                        "thread": thread_deref_placeholder(thread_number),
-                        "operation": bson::to_bson(&new_op).unwrap()
+                    Err(e) => {
+                        batch.complain(format_args!("error: {}", e));
+                        batch.record("FAIL", &path, &e);
+                        out_of_date = true;
                     }
-                    .into();
                 }
-                "waitForThread" => {
-                    let thread_name = arguments.as_ref().unwrap().get_str("name").unwrap();
-                    let thread_number = Operation::thread_number(thread_name);
-                    arguments = doc! {
-                        "thread": thread_deref_placeholder(thread_number)
+                continue;
+            }
+            let converted = batch.force || !out.exists() || !state.is_current(&filename, &input);
+            if converted {
+                batch.progress(format_args!("converting {}", filename));
+                let converted = convert_str(&input, options)
+                    .and_then(|(new, report)| Ok((format.render(new)?, report)));
+                let (new, report) = match converted {
+                    Ok(converted) => converted,
+                    Err(e) => {
+                        batch.complain(format_args!("error: {}", e));
+                        batch.record("FAIL", &path, &e);
+                        summary.add_skipped(relative(&path), &e);
+                        state.forget(&filename);
+                        continue;
                     }
-                    .into();
-                }
-                "configureFailPoint" => {
-                    object = "testRunner".to_string();
-                    name = "failPoint".to_string();
-                    arguments.as_mut().unwrap().insert("client", SETUP_CLIENT_DEREF_PLACEHOLDER);
+                };
+                summary.add_converted(&relative(&path), &input, &relative(&out), &report);
+                for warning in report.warnings {
+                    batch.complain(format_args!("warning: {}", warning));
                 }
-                _ => {}
-            };
-
-            let (expect_result, expect_error) = match old_op.result {
-                Some(OperationResult::Success(b)) => (Some(b), None),
-                Some(OperationResult::Error(e)) => (
-                    None,
-                    ExpectError {
-                        is_error: None,
-                        error_contains: e.error_contains,
-                        error_code: e.error_code,
-                        error_code_name: None,
-                        error_labels_contain: e.error_labels_contain,
-                        error_labels_omit: e.error_labels_omit,
-                    }
-                    .into(),
-                ),
-                None if old_op.error.unwrap_or(false) => (
-                    None,
-                    Some(ExpectError {
-                        is_error: Some(true),
-                        ..Default::default()
-                    }),
-                ),
-                _ => (None, None),
-            };
-
-            Self {
-                name,
-                object,
-                arguments,
-                save_result_as_entity: None,
-                expect_result,
-                expect_error,
+                let _span = tracing::debug_span!("write", file = %filename).entered();
+                let mut new_file = File::create(&out)?;
+                new_file.write_all(new.as_bytes())?;
+                state.record(&filename, &input);
+            }
+            if converted {
+                batch.record("OK", &path, out.display());
+            } else {
+                batch.record("SKIP", &path, "unchanged since the last conversion");
             }
         }
-
-        fn thread_number(v2_name: impl AsRef<str>) -> usize {
-            v2_name
-                .as_ref()
-                .strip_prefix("thread")
-                .unwrap()
-                .parse::<usize>()
-                .unwrap()
-                - 1
-        }
-    }
-
-    #[derive(Debug, Default, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct ExpectEvents {
-        client: String,
-        event_type: String,
-        events: Vec<ExpectEvent>,
     }
-
-    #[derive(Debug, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub enum ExpectEvent {
-        #[serde(rename_all = "camelCase")]
-        CommandStartedEvent {
-            command: Document,
-            command_name: Option<String>,
-            database_name: Option<String>,
-        },
-    }
-
-    #[serde_with::skip_serializing_none]
-    #[derive(Debug, Default, Serialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct ExpectError {
-        is_error: Option<bool>,
-        error_contains: Option<String>,
-        error_code: Option<i32>,
-        error_code_name: Option<String>,
-        error_labels_contain: Option<Vec<String>>,
-        error_labels_omit: Option<Vec<String>>,
+    options.hoist_entities = hoist_entities;
+    check_naming(unified, options.entity_naming, batch)?;
+    if batch.check {
+        return Ok(!out_of_date);
     }
-}
+    state.save(&state_path)?;
 
-static CLIENT_DEFINITION_PLACEHOLDER: &'static str = "xCLIENT_DEFINITION_PLACEHOLDER";
-static CLIENT_DEREF_PLACEHOLDER: &'static str = "xCLIENT_DEREF_PLACEHOLDER";
-
-static DATABASE_DEFINITION_PLACEHOLDER: &'static str = "xDATABASE_DEFINITION_PLACEHOLDER";
-static DATABASE_DEREF_PLACEHOLDER: &'static str = "xDATABASE_DEREF_PLACEHOLDER";
-static DATABASE_NAME_DEFINITION_PLACEHOLDER: &'static str = "xDATABASE_NAME_DEFINITION_PLACEHOLDER";
-static DATABASE_NAME_DEREF_PLACEHOLDER: &'static str = "xDATABASE_NAME_DEREF_PLACEHOLDER";
-
-static COLLECTION_DEFINITION_PLACEHOLDER: &'static str = "xCOLLECTION_DEFINITION_PLACEHOLDER";
-static COLLECTION_DEREF_PLACEHOLDER: &'static str = "xCOLLECTION_DEREF_PLACEHOLDER";
-static COLLECTION_NAME_DEFINITION_PLACEHOLDER: &'static str =
-    "COLLECTION_NAME_DEFINITION_PLACEHOLDER";
-static COLLECTION_NAME_DEREF_PLACEHOLDER: &'static str = "xCOLLECTION_NAME_DEREF_PLACEHOLDER";
-
-static SETUP_CLIENT_DEFINITION_PLACEHOLDER: &'static str = "xSETUP_CLIENT_DEFINITION_PLACEHOLDER";
-static SETUP_CLIENT_DEREF_PLACEHOLDER: &'static str = "xSETUP_CLIENT_DEREF_PLACEHOLDER";
-
-static ADMIN_DATABASE_DEFINITION_PLACEHOLDER: &'static str =
-    "xADMIN_DATABASE_DEFINITION_PLACEHOLDER";
-static ADMIN_DATABASE_DEREF_PLACEHOLDER: &'static str = "xADMIN_DATABASE_DEREF_PLACEHOLDER";
-
-static TOPOLOGY_DESCRIPTION_DEFINITION_PLACEHOLDER: &'static str = "xTDESC_DEFINITION_PLACEHOLDER";
-static TOPOLOGY_DESCRIPTION_DEREF_PLACEHOLDER: &'static str = "xTDESC_DEREF_PLACEHOLDER";
-
-static REGEX_PLACEHOLDER_REPLACEMENTS: &'static [(&'static str, &'static str)] = &[
-    (CLIENT_DEFINITION_PLACEHOLDER, "&client client"),
-    (CLIENT_DEREF_PLACEHOLDER, "*client"),
-    (DATABASE_DEFINITION_PLACEHOLDER, "&database database"),
-    (DATABASE_DEREF_PLACEHOLDER, "*database"),
-    (
-        DATABASE_NAME_DEFINITION_PLACEHOLDER,
-        "&databaseName sdam-tests",
-    ),
-    (DATABASE_NAME_DEREF_PLACEHOLDER, "*databaseName"),
-    (COLLECTION_DEFINITION_PLACEHOLDER, "&collection collection"),
-    (COLLECTION_DEREF_PLACEHOLDER, "*collection"),
-    (COLLECTION_NAME_DEREF_PLACEHOLDER, "*collectionName"),
-    ("initialData:", "initialData: &initialData"),
-    (
-        SETUP_CLIENT_DEFINITION_PLACEHOLDER,
-        "&setupClient setupClient",
-    ),
-    (SETUP_CLIENT_DEREF_PLACEHOLDER, "*setupClient"),
-    (
-        ADMIN_DATABASE_DEFINITION_PLACEHOLDER,
-        "&adminDatabase adminDatabase",
-    ),
-    (ADMIN_DATABASE_DEREF_PLACEHOLDER, "*adminDatabase"),
-    ("THREAD_(\\d+)_DEFINITION_PLACEHOLDER", "&thread$1 thread$1"),
-    ("THREAD_(\\d+)_DEREF_PLACEHOLDER", "*thread$1"),
-    (TOPOLOGY_DESCRIPTION_DEFINITION_PLACEHOLDER, "&topologyDescription topologyDescription"),
-    (TOPOLOGY_DESCRIPTION_DEREF_PLACEHOLDER, "*topologyDescription"),
-];
-
-fn thread_definition_placeholder(i: usize) -> String {
-    format!("THREAD_{}_DEFINITION_PLACEHOLDER", i)
+    Ok(true)
 }
 
-fn thread_deref_placeholder(i: usize) -> String {
-    format!("THREAD_{}_DEREF_PLACEHOLDER", i)
-}
+/// Checks that the unified files in a directory all name their entities according to `naming`,
+/// renaming the entities of the ones that don't if asked to.
+fn check_naming(dir: &Path, naming: EntityNaming, batch: &Batch) -> Result<()> {
+    let mut mismatched = Vec::new();
+    for path in yaml_files(dir)? {
+        let yaml = std::fs::read_to_string(&path)?;
+        match naming::detect(&yaml) {
+            Some(file_naming) if file_naming != naming => {
+                mismatched.push((path, yaml, file_naming))
+            }
+            _ => {}
+        }
+    }
+    if mismatched.is_empty() {
+        return Ok(());
+    }
 
-fn convert(file_name: impl AsRef<str>, old: crud_v2::TestFile) -> Result<String> {
-    let mut ents = Vec::new();
-    let mut tests = Vec::new();
-    let contains_admin_command = old.tests.iter().any(|old_test| {
-        old_test
-            .operations
+    if !batch.rename_entities {
+        let files: Vec<_> = mismatched
             .iter()
-            .any(|op| op.name.as_str() == "runAdminCommand")
-    });
-    let contains_fail_point = old.tests.iter().any(|t| {
-        t.fail_point.is_some()
-            || t.operations
-                .iter()
-                .any(|op| op.name.as_str() == "configureFailPoint")
-    });
-
-    for (i, old_test) in old.tests.into_iter().enumerate() {
-        // if !create_entities_in_tests {
-        //     ents.push(CreateEntity::Client(ClientEntity {
-        //         id: format!("$CLIENT_{}_DEFINITION_PLACEHOLDER$", i),
-        //         observe_events: Some(old_test.observed_events()),
-        //         uri_options: old_test.client_uri.clone(),
-        //     }));
-
-        //     ents.push(CreateEntity::Database(DatabaseEntity {
-        //         id: format!("$DATABASE_{}_DEFINITION_PLACEHOLDER$", i),
-        //         client: format!("$CLIENT_{}_DEREF_PLACEHOLDER$", i),
-        //         database_name: format!("$DATABASE_{}_NAME_DEFINITION_PLACEHOLDER$", i),
-        //     }));
-
-        //     ents.push(CreateEntity::Collection(CollectionEntity {
-        //         id: format!("$COLLECTION_{}_DEFINITION_PLACEHOLDER$", i),
-        //         database: format!("$DATABASE_{}_DEREF_PLACEHOLDER$", i),
-        //         collection_name: format!("$COLLECTION_{}_NAME_DEFINITION_PLACEHOLDER$", i),
-        //     }));
-        // }
-
-        tests.push(Test::from_crud_v2(old_test, i));
+            .map(|(path, ..)| path.file_name().unwrap().to_string_lossy())
+            .collect();
+        batch.complain(format_args!(
+            "warning: {} in {} name their entities differently from --entity-naming, pass \
+             --rename-entities to rename them",
+            files.join(", "),
+            dir.display()
+        ));
+        return Ok(());
     }
-
-    let initial_data = match old.data {
-        TestData::Single(docs) => {
-            vec![InitialData {
-                collection_name: COLLECTION_NAME_DEFINITION_PLACEHOLDER.to_string(),
-                database_name: DATABASE_NAME_DEFINITION_PLACEHOLDER.to_string(),
-                documents: docs,
-            }]
-        }
-        _ => panic!("got map of data"),
-    };
-
-    if contains_fail_point || contains_admin_command {
-        ents.push(CreateEntity::Client(ClientEntity {
-            id: SETUP_CLIENT_DEFINITION_PLACEHOLDER.to_string(),
-            observe_events: None,
-            uri_options: None,
-        }));
-
-        if contains_admin_command {
-            ents.push(CreateEntity::Database(DatabaseEntity {
-                id: ADMIN_DATABASE_DEFINITION_PLACEHOLDER.to_string(),
-                client: SETUP_CLIENT_DEREF_PLACEHOLDER.to_string(),
-                database_name: "admin".to_string(),
-            }))
+    for (path, yaml, file_naming) in mismatched {
+        match naming::rename(&yaml, file_naming, naming) {
+            Ok(renamed) => {
+                batch.progress(format_args!("renaming entities in {}", path.display()));
+                std::fs::write(&path, renamed)?;
+            }
+            Err(e) => batch.complain(format_args!(
+                "error: can't rename the entities in {}: {}",
+                path.display(),
+                e
+            )),
         }
     }
-
-    let test_file = unified::TestFile {
-        description: file_name.as_ref().to_string(),
-        schema_version: "1.10".to_string(),
-        run_on_requirements: old
-            .run_on
-            .map(|run_on| run_on.into_iter().map(From::from).collect()),
-        create_entities: Some(ents),
-        initial_data: Some(initial_data),
-        tests,
-    };
-
-    let mut raw_string = serde_yaml::to_string(&test_file)?;
-
-    for (regex_str, replacement) in REGEX_PLACEHOLDER_REPLACEMENTS {
-        let regex = Regex::new(regex_str).unwrap();
-        raw_string = regex.replace_all(&raw_string, *replacement).to_string();
-    }
-
-    let regex = Regex::new(COLLECTION_NAME_DEFINITION_PLACEHOLDER).unwrap();
-    raw_string = regex
-        .replace_all(
-            &raw_string,
-            format!("&collectionName {}", old.collection_name).as_str(),
-        )
-        .to_string();
-
-    Ok(raw_string)
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    // let file =
-    // File::open("/home/patrick/specifications/source/server-discovery-and-monitoring/tests/
-    // integration/auth-error.yml")?; multiple tests
-    // let file =
-    // File::open("/home/patrick/specifications/source/server-discovery-and-monitoring/tests/
-    // integration/hello-timeout.yml")?;
-    // let file = File::open(
-    //     "/home/patrick/specifications/source/server-discovery-and-monitoring/tests/integration/\
-    //      rediscover-quickly-after-step-down.yml",
-    // )?;
-
-    // threads
-    // let file = File::open(
-    //     "/home/patrick/specifications/source/server-discovery-and-monitoring/tests/integration/
-    // find-shutdown-error.yml", )?;
-
-    let tests_dir =
-        PathBuf::from("/home/patrick/specifications/source/server-discovery-and-monitoring/tests/");
-    let integration = tests_dir.join("integration");
-    let unified = tests_dir.join("unified");
-
-    let paths = std::fs::read_dir(integration)?;
-
-    for path in paths {
-        let path = path?;
-        if path.path().extension().unwrap() != "yml" {
-            continue;
+/// The names of the files directly inside a directory that git says have changed since a ref,
+/// either in commits, uncommitted changes, or as new untracked files.
+fn changed_files(dir: &Path, since: &str) -> Result<BTreeSet<String>> {
+    let git = |args: &[&str]| -> Result<String> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
         }
-        let filename = path
-            .path()
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
-
-        println!("converting {}", filename);
-        let out = unified.join(filename.as_str());
-        let old_file = File::open(path.path())?;
-        let old: crud_v2::TestFile = serde_yaml::from_reader(old_file)?;
-        let new = convert(filename.strip_suffix(".yml").unwrap(), old)?;
-        let mut new_file = File::create(out)?;
-        new_file.write_all(new.as_bytes())?;
-        // println!("{}", new);
-        // break;
-    }
-    // println!("{}", new);
-
-    Ok(())
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let changed = git(&["diff", "--name-only", "--relative", since, "--", "."])?;
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "--", "."])?;
+    Ok(changed
+        .lines()
+        .chain(untracked.lines())
+        .filter(|path| !path.contains('/'))
+        .map(String::from)
+        .collect())
 }
@@ -0,0 +1,81 @@
+//! Merging legacy test files into one, for spec families whose legacy tests are spread over many
+//! small files that share the same requirements and data.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{diff, ConversionError};
+
+/// Groups the names of legacy test files, given along with their YAML contents, by the files
+/// they can be merged with, i.e. the ones with the same fields other than `tests`. Groups are
+/// in the order of their first files, and files that don't parse are in groups of their own.
+pub fn mergeable_groups(files: &[(String, String)]) -> Vec<Vec<String>> {
+    let mut groups: Vec<(Option<Value>, Vec<String>)> = Vec::new();
+    for (name, input) in files {
+        let shared = serde_yaml::from_str::<Mapping>(input).ok().map(|mut file| {
+            file.remove(&Value::from("tests"));
+            Value::Mapping(file)
+        });
+        let group = groups
+            .iter_mut()
+            .find(|(group_shared, _)| match (group_shared, &shared) {
+                (Some(group_shared), Some(shared)) => diff::diff(group_shared, shared).is_empty(),
+                _ => false,
+            });
+        match group {
+            Some((_, names)) => names.push(name.clone()),
+            None => groups.push((shared, vec![name.clone()])),
+        }
+    }
+    groups.into_iter().map(|(_, names)| names).collect()
+}
+
+/// Merges the YAML contents of legacy test files, given along with their names, into a single
+/// legacy file with all of their tests. Every field other than `tests`, e.g. `runOn` and
+/// `data`, has to be the same in all of the files. Tests whose descriptions are already taken
+/// by an earlier file have the name of their file appended to them.
+pub fn merge_files(files: &[(String, String)]) -> Result<String, ConversionError> {
+    let mut merged: Option<Mapping> = None;
+    let mut tests = Vec::new();
+    let mut descriptions = Vec::new();
+    for (name, input) in files {
+        let mut file: Mapping =
+            serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(input))
+                .map_err(|e| ConversionError::from(e).in_file(name.as_str()))?;
+        let file_tests = match file.remove(&Value::from("tests")) {
+            Some(Value::Sequence(tests)) => tests,
+            _ => Vec::new(),
+        };
+
+        match merged {
+            Some(ref merged) => {
+                let difference = diff::diff(
+                    &Value::Mapping(merged.clone()),
+                    &Value::Mapping(file.clone()),
+                )
+                .into_iter()
+                .next();
+                if let Some(difference) = difference {
+                    return Err(ConversionError::Unmergeable {
+                        file: name.clone(),
+                        difference: Box::new(difference),
+                    });
+                }
+            }
+            None => merged = Some(file),
+        }
+
+        for mut test in file_tests {
+            if let Some(Value::String(description)) = test.get_mut("description") {
+                if descriptions.contains(description) {
+                    *description = format!("{} ({})", description, name);
+                }
+                descriptions.push(description.clone());
+            }
+            tests.push(test);
+        }
+    }
+
+    let mut merged = merged.unwrap_or_default();
+    merged.insert("tests".into(), Value::Sequence(tests));
+    serde_yaml::to_string(&merged).map_err(ConversionError::Emit)
+}
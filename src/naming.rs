@@ -0,0 +1,149 @@
+//! Consistency of entity naming across the unified test files in a directory, which is easier
+//! to review when every file names its entities the same way.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_yaml::Value;
+
+use crate::{
+    diff,
+    entities::{Entity, EntityNaming},
+    ConversionError,
+};
+
+/// The entities whose ids depend on the naming.
+static NAMED_ENTITIES: &[Entity] = &[
+    Entity::Client,
+    Entity::Database,
+    Entity::Collection,
+    Entity::Bucket,
+];
+
+/// The keys whose values are entity ids.
+static ID_KEYS: &[&str] = &["id", "object", "client", "database", "collection", "bucket"];
+
+/// Matches a plain scalar that's the whole value of one of the `ID_KEYS`, possibly anchored.
+static ID_VALUE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?m)^(\s*(?:- )?(?:{}): (?:&\w+ )?)(\w+)$",
+        ID_KEYS.join("|")
+    ))
+    .unwrap()
+});
+
+/// The naming a unified test file uses, judged by the id of its primary client. Files without
+/// one, e.g. because they only use a setup client, don't have a naming.
+pub fn detect(yaml: &str) -> Option<EntityNaming> {
+    let file: Value = serde_yaml::from_str(yaml).ok()?;
+    let mut ids = Vec::new();
+    collect_client_ids(&file, &mut ids);
+    [EntityNaming::Descriptive, EntityNaming::Numbered]
+        .into_iter()
+        .find(|naming| ids.contains(&Entity::Client.id(*naming)))
+}
+
+fn collect_client_ids(value: &Value, ids: &mut Vec<String>) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                if let (Some("client"), Some(id)) = (key.as_str(), value.get("id")) {
+                    ids.extend(id.as_str().map(String::from));
+                }
+                collect_client_ids(value, ids);
+            }
+        }
+        Value::Sequence(values) => values
+            .iter()
+            .for_each(|value| collect_client_ids(value, ids)),
+        _ => {}
+    }
+}
+
+/// Renames the entities of a unified test file that uses the naming `from` to the ones `to`
+/// would give them, keeping its anchors, comments, and formatting. The renamed file is checked
+/// to be the same as the original apart from the ids.
+pub fn rename(yaml: &str, from: EntityNaming, to: EntityNaming) -> Result<String, ConversionError> {
+    let original: Value = serde_yaml::from_str(yaml).map_err(ConversionError::Malformed)?;
+    let renames: Vec<_> = NAMED_ENTITIES
+        .iter()
+        .map(|entity| (entity.id(from), entity.id(to)))
+        .filter(|(from, to)| from != to)
+        .collect();
+
+    let mut ids = Vec::new();
+    collect_ids(&original, &mut ids);
+    for (from, to) in renames.iter() {
+        if ids.contains(to) && !renames.iter().any(|(renamed, _)| renamed == to) {
+            return Err(ConversionError::EntityIdConflict {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+    }
+
+    let mut renamed = yaml.to_string();
+    for (from, to) in renames.iter() {
+        let anchor = Regex::new(&format!(r"([&*]){}\b", regex::escape(from))).unwrap();
+        renamed = anchor
+            .replace_all(&renamed, format!("${{1}}{}", to).as_str())
+            .into_owned();
+    }
+    renamed = ID_VALUE
+        .replace_all(&renamed, |captures: &Captures| {
+            let id = &captures[2];
+            let id = renames
+                .iter()
+                .find(|(from, _)| from == id)
+                .map_or(id, |(_, to)| to.as_str());
+            format!("{}{}", &captures[1], id)
+        })
+        .into_owned();
+
+    let mut expected = original;
+    rename_ids(&mut expected, &renames);
+    let actual: Value = serde_yaml::from_str(&renamed).map_err(ConversionError::Malformed)?;
+    match diff::diff(&expected, &actual).into_iter().next() {
+        Some(difference) => Err(ConversionError::RoundTrip(Box::new(difference))),
+        None => Ok(renamed),
+    }
+}
+
+/// The values of the `ID_KEYS` anywhere in a file.
+fn collect_ids(value: &Value, ids: &mut Vec<String>) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                if let (Some(key), Some(id)) = (key.as_str(), value.as_str()) {
+                    if ID_KEYS.contains(&key) {
+                        ids.push(id.to_string());
+                    }
+                }
+                collect_ids(value, ids);
+            }
+        }
+        Value::Sequence(values) => values.iter().for_each(|value| collect_ids(value, ids)),
+        _ => {}
+    }
+}
+
+fn rename_ids(value: &mut Value, renames: &[(String, String)]) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                let is_id = key.as_str().is_some_and(|key| ID_KEYS.contains(&key));
+                match value {
+                    Value::String(id) if is_id => {
+                        if let Some((_, to)) = renames.iter().find(|(from, _)| from == id) {
+                            *id = to.clone();
+                        }
+                    }
+                    value => rename_ids(value, renames),
+                }
+            }
+        }
+        Value::Sequence(values) => values
+            .iter_mut()
+            .for_each(|value| rename_ids(value, renames)),
+        _ => {}
+    }
+}
@@ -0,0 +1,415 @@
+//! Normalization of legacy arguments and options into the forms the unified format expects.
+
+use bson::{doc, oid::ObjectId, Bson, Document};
+
+use crate::report::{WarningKind, Warnings};
+
+/// The canonical names of the connection string options that a unified client entity's
+/// `uriOptions` may contain.
+static URI_OPTIONS: &[&str] = &[
+    "appname",
+    "authMechanism",
+    "authMechanismProperties",
+    "authSource",
+    "compressors",
+    "connectTimeoutMS",
+    "directConnection",
+    "heartbeatFrequencyMS",
+    "journal",
+    "loadBalanced",
+    "localThresholdMS",
+    "maxConnecting",
+    "maxIdleTimeMS",
+    "maxPoolSize",
+    "maxStalenessSeconds",
+    "minPoolSize",
+    "readConcernLevel",
+    "readPreference",
+    "readPreferenceTags",
+    "replicaSet",
+    "retryReads",
+    "retryWrites",
+    "serverMonitoringMode",
+    "serverSelectionTimeoutMS",
+    "socketTimeoutMS",
+    "srvMaxHosts",
+    "srvServiceName",
+    "timeoutMS",
+    "tls",
+    "tlsAllowInvalidCertificates",
+    "tlsAllowInvalidHostnames",
+    "tlsCAFile",
+    "tlsCertificateKeyFile",
+    "tlsInsecure",
+    "w",
+    "waitQueueTimeoutMS",
+    "wTimeoutMS",
+    "zlibCompressionLevel",
+];
+
+/// Legacy `clientOptions` keys that correspond to a differently named URI option.
+static URI_OPTION_ALIASES: &[(&str, &str)] = &[("j", "journal"), ("wtimeout", "wTimeoutMS")];
+
+fn canonical_uri_option(key: &str) -> Option<&'static str> {
+    if let Some((_, option)) = URI_OPTION_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(key))
+    {
+        return Some(option);
+    }
+    URI_OPTIONS
+        .iter()
+        .find(|option| option.eq_ignore_ascii_case(key))
+        .copied()
+}
+
+/// Removes the `autoEncryptOpts` from a legacy `clientOptions` document, returning them in
+/// the form the unified client entity expects.
+pub(crate) fn auto_encrypt_opts(
+    client_options: &mut Document,
+    warnings: &Warnings,
+) -> Option<Document> {
+    let mut opts = match client_options.remove("autoEncryptOpts")? {
+        Bson::Document(opts) => opts,
+        _ => {
+            warnings.warn(WarningKind::DroppedField {
+                field: "clientOptions.autoEncryptOpts".to_string(),
+                reason: "it is not a document",
+            });
+            return None;
+        }
+    };
+    // the legacy runners defaulted the key vault namespace, but it's required in the unified
+    // format.
+    if !opts.contains_key("keyVaultNamespace") {
+        opts.insert("keyVaultNamespace", "keyvault.datakeys");
+    }
+    if let Ok(kms_providers) = opts.get_document_mut("kmsProviders") {
+        kms_provider_placeholders(kms_providers);
+    }
+    Some(opts)
+}
+
+/// The credentials of each KMS provider that runners fill in at runtime.
+static KMS_CREDENTIALS: &[(&str, &[&str])] = &[
+    ("aws", &["accessKeyId", "secretAccessKey"]),
+    ("azure", &["tenantId", "clientId", "clientSecret"]),
+    ("gcp", &["email", "privateKey"]),
+    ("kmip", &["endpoint"]),
+    ("local", &["key"]),
+];
+
+/// Legacy runners filled in the credentials of KMS providers given as empty documents from their
+/// environment, whereas the unified format marks each credential with a `$$placeholder`. The
+/// legacy temporary AWS credential providers are plain `aws` providers with a session token, or
+/// without one to test that it's required, unless there's already an `aws` provider.
+fn kms_provider_placeholders(kms_providers: &mut Document) {
+    let placeholder = || Bson::from(doc! { "$$placeholder": 1 });
+    let has_aws = kms_providers.contains_key("aws");
+    for (provider, credentials) in std::mem::take(kms_providers) {
+        let (provider, session_token) = match provider.as_str() {
+            "awsTemporary" if !has_aws => ("aws".to_string(), true),
+            "awsTemporaryNoSessionToken" if !has_aws => ("aws".to_string(), false),
+            _ => (provider, false),
+        };
+        let names = KMS_CREDENTIALS
+            .iter()
+            .find(|(name, _)| *name == provider)
+            .map(|(_, names)| *names);
+        let credentials = match (credentials, names) {
+            (Bson::Document(credentials), Some(names)) if credentials.is_empty() => {
+                let mut credentials: Document = names
+                    .iter()
+                    .map(|name| (name.to_string(), placeholder()))
+                    .collect();
+                if session_token {
+                    credentials.insert("sessionToken", placeholder());
+                }
+                credentials.into()
+            }
+            (credentials, _) => credentials,
+        };
+        kms_providers.insert(provider, credentials);
+    }
+}
+
+/// Converts a legacy `clientOptions` document into the equivalent unified `uriOptions`,
+/// normalizing option names and flattening read/write concern documents into their URI
+/// option counterparts. Options that can't be expressed as URI options are dropped with a
+/// warning.
+pub(crate) fn uri_options(client_options: Document, warnings: &Warnings) -> Document {
+    let mut uri_options = Document::new();
+    for (key, value) in client_options {
+        match (key.as_str(), value) {
+            ("readConcern", Bson::Document(read_concern)) => {
+                for (key, value) in read_concern {
+                    match key.as_str() {
+                        "level" => {
+                            uri_options.insert("readConcernLevel", value);
+                        }
+                        _ => warnings.warn(WarningKind::DroppedField {
+                            field: format!("clientOptions.readConcern.{}", key),
+                            reason: "it cannot be expressed as a URI option",
+                        }),
+                    }
+                }
+            }
+            ("writeConcern", Bson::Document(write_concern)) => {
+                for (key, value) in write_concern {
+                    match canonical_uri_option(&key) {
+                        Some(option @ ("w" | "journal" | "wTimeoutMS")) => {
+                            let field = format!("clientOptions.writeConcern.{}", key);
+                            let value = typed_uri_option(option, value, field, warnings);
+                            uri_options.insert(option, value);
+                        }
+                        _ => warnings.warn(WarningKind::DroppedField {
+                            field: format!("clientOptions.writeConcern.{}", key),
+                            reason: "it cannot be expressed as a URI option",
+                        }),
+                    }
+                }
+            }
+            ("readPreference", Bson::Document(read_preference)) => {
+                for (key, value) in read_preference {
+                    let field = format!("clientOptions.readPreference.{}", key);
+                    match (key.as_str(), value) {
+                        ("mode", Bson::String(mode)) => {
+                            uri_options.insert("readPreference", normalize_mode(&mode));
+                        }
+                        ("tags", Bson::Array(tag_sets)) => match read_preference_tags(&tag_sets) {
+                            Some(tags) => {
+                                uri_options.insert("readPreferenceTags", tags);
+                            }
+                            None => warnings.warn(WarningKind::DroppedField {
+                                field,
+                                reason: "its tag sets aren't all documents of strings",
+                            }),
+                        },
+                        ("maxStalenessSeconds", value) => {
+                            let value =
+                                typed_uri_option("maxStalenessSeconds", value, field, warnings);
+                            uri_options.insert("maxStalenessSeconds", value);
+                        }
+                        _ => warnings.warn(WarningKind::DroppedField {
+                            field,
+                            reason: "it cannot be expressed as a URI option",
+                        }),
+                    }
+                }
+            }
+            ("readPreference", Bson::String(mode)) => {
+                uri_options.insert("readPreference", normalize_mode(&mode));
+            }
+            (key, value) => match canonical_uri_option(key) {
+                Some(option) => {
+                    let field = format!("clientOptions.{}", key);
+                    let value = typed_uri_option(option, value, field, warnings);
+                    uri_options.insert(option, value);
+                }
+                None => warnings.warn(WarningKind::DroppedField {
+                    field: format!("clientOptions.{}", key),
+                    reason: "it is not a URI option",
+                }),
+            },
+        }
+    }
+    uri_options
+}
+
+/// The `readPreferenceTags` URI option equivalent to a read preference's tag sets, each written
+/// as comma-separated `name:value` pairs, or `None` if a tag set isn't a document of strings.
+fn read_preference_tags(tag_sets: &[Bson]) -> Option<Vec<String>> {
+    tag_sets
+        .iter()
+        .map(|tag_set| {
+            let tags = tag_set
+                .as_document()?
+                .iter()
+                .map(|(name, value)| Some(format!("{}:{}", name, value.as_str()?)))
+                .collect::<Option<Vec<_>>>()?;
+            Some(tags.join(","))
+        })
+        .collect()
+}
+
+/// Converts a legacy session's options into the unified session entity's `sessionOptions`,
+/// normalizing the read preference and `maxCommitTimeMS` of its `defaultTransactionOptions`.
+/// Options the unified format doesn't have are dropped with a warning.
+pub(crate) fn session_options(options: Document, warnings: &Warnings) -> Document {
+    let mut session_options = Document::new();
+    for (key, value) in options {
+        match (key.as_str(), value) {
+            ("causalConsistency" | "snapshot", value) => {
+                session_options.insert(key, value);
+            }
+            ("defaultTransactionOptions", Bson::Document(mut transaction_options)) => {
+                read_preference(&mut transaction_options);
+                max_commit_time_ms(&mut transaction_options);
+                let mut default_transaction_options = Document::new();
+                for (key, value) in transaction_options {
+                    match key.as_str() {
+                        "readConcern" | "writeConcern" | "readPreference" | "maxCommitTimeMS" => {
+                            default_transaction_options.insert(key, value);
+                        }
+                        _ => warnings.warn(WarningKind::DroppedField {
+                            field: format!("sessionOptions.defaultTransactionOptions.{}", key),
+                            reason: "it is not a transaction option",
+                        }),
+                    }
+                }
+                session_options.insert("defaultTransactionOptions", default_transaction_options);
+            }
+            (key, _) => warnings.warn(WarningKind::DroppedField {
+                field: format!("sessionOptions.{}", key),
+                reason: "it is not a session option",
+            }),
+        }
+    }
+    session_options
+}
+
+/// The URI options whose values are booleans.
+static BOOLEAN_URI_OPTIONS: &[&str] = &[
+    "directConnection",
+    "journal",
+    "loadBalanced",
+    "retryReads",
+    "retryWrites",
+    "tls",
+    "tlsAllowInvalidCertificates",
+    "tlsAllowInvalidHostnames",
+    "tlsInsecure",
+];
+
+/// The URI options whose values are integers.
+static INTEGER_URI_OPTIONS: &[&str] = &[
+    "connectTimeoutMS",
+    "heartbeatFrequencyMS",
+    "localThresholdMS",
+    "maxConnecting",
+    "maxIdleTimeMS",
+    "maxPoolSize",
+    "maxStalenessSeconds",
+    "minPoolSize",
+    "serverSelectionTimeoutMS",
+    "socketTimeoutMS",
+    "srvMaxHosts",
+    "timeoutMS",
+    "waitQueueTimeoutMS",
+    "wTimeoutMS",
+    "zlibCompressionLevel",
+];
+
+/// Coerces the value of a URI option into the type the unified runner expects, since legacy
+/// files sometimes quote them the way they'd appear in a connection string.
+fn typed_uri_option(option: &str, value: Bson, field: String, warnings: &Warnings) -> Bson {
+    let coerced = if BOOLEAN_URI_OPTIONS.contains(&option) {
+        match value {
+            Bson::String(ref s) => s
+                .to_ascii_lowercase()
+                .parse::<bool>()
+                .ok()
+                .map(|b| (b.into(), "a boolean")),
+            _ => None,
+        }
+    } else if INTEGER_URI_OPTIONS.contains(&option) {
+        match value {
+            // int64s are only narrowed, which doesn't change how they're written.
+            Bson::Int64(_) => return integer(&value).unwrap_or(value),
+            _ => integer(&value).map(|integer| (integer, "an integer")),
+        }
+    } else if option == "w" {
+        match value {
+            Bson::String(_) => integer(&value).map(|integer| (integer, "an integer")),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    match coerced {
+        Some((coerced, expected)) => {
+            warnings.warn(WarningKind::CoercedValue {
+                field,
+                value: match value {
+                    // e.g. 100.0 rather than 100, which would look like it's already an integer.
+                    Bson::Double(d) => format!("{:?}", d),
+                    value => value.to_string(),
+                },
+                expected,
+            });
+            coerced
+        }
+        None => value,
+    }
+}
+
+/// Normalizes a read preference mode into the casing used by the unified format.
+fn normalize_mode(mode: &str) -> String {
+    match mode.to_ascii_lowercase().as_str() {
+        "primary" => "primary",
+        "primarypreferred" => "primaryPreferred",
+        "secondary" => "secondary",
+        "secondarypreferred" => "secondaryPreferred",
+        "nearest" => "nearest",
+        _ => mode,
+    }
+    .to_string()
+}
+
+/// Normalizes a `timeoutMS` in the given arguments or options into an integer, since legacy
+/// files sometimes give it as a double or a string.
+pub(crate) fn timeout_ms(document: &mut Document) {
+    if let Some(timeout) = document.get("timeoutMS").and_then(integer) {
+        document.insert("timeoutMS", timeout);
+    }
+}
+
+/// Normalizes a `maxCommitTimeMS` in the given transaction options into an integer, like
+/// `timeout_ms`.
+pub(crate) fn max_commit_time_ms(document: &mut Document) {
+    if let Some(max_commit_time) = document.get("maxCommitTimeMS").and_then(integer) {
+        document.insert("maxCommitTimeMS", max_commit_time);
+    }
+}
+
+/// The integer a numeric or string value represents, as an int32 if it fits. Values that are
+/// already int32s, or that don't represent an integer, give `None`.
+pub(crate) fn integer(value: &Bson) -> Option<Bson> {
+    let integer = match value {
+        Bson::Int64(i) => *i,
+        Bson::Double(d) if d.fract() == 0.0 && d.abs() < i64::MAX as f64 => *d as i64,
+        Bson::String(s) => s.trim().parse().ok()?,
+        _ => return None,
+    };
+    Some(
+        i32::try_from(integer)
+            .map(Bson::Int32)
+            .unwrap_or(Bson::Int64(integer)),
+    )
+}
+
+/// Normalizes the given argument into an ObjectId if it's specified as a hex string.
+pub(crate) fn object_id(arguments: &mut Document, key: &str) {
+    let oid = match arguments.get_str(key).map(ObjectId::parse_str) {
+        Ok(Ok(oid)) => oid,
+        _ => return,
+    };
+    arguments.insert(key, oid);
+}
+
+/// Normalizes the `readPreference` in the given arguments, if any, into the unified document
+/// form. Legacy files specify it as either a bare mode string or a document with a `mode`.
+pub(crate) fn read_preference(arguments: &mut Document) {
+    let read_preference = match arguments.get_mut("readPreference") {
+        Some(Bson::String(mode)) => doc! { "mode": normalize_mode(mode) },
+        Some(Bson::Document(read_preference)) => {
+            if let Ok(mode) = read_preference.get_str("mode") {
+                let mode = normalize_mode(mode);
+                read_preference.insert("mode", mode);
+            }
+            return;
+        }
+        _ => return,
+    };
+    arguments.insert("readPreference", read_preference);
+}
@@ -0,0 +1,16 @@
+use bson::doc;
+
+use super::{OperationContext, OperationRegistry};
+use crate::{unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry.register("watch", watch);
+}
+
+fn watch(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    op.name = "createChangeStream".to_string();
+    if op.arguments.is_none() {
+        op.arguments = doc! { "pipeline": [] }.into();
+    }
+    Ok(())
+}
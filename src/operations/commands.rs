@@ -0,0 +1,52 @@
+use bson::Document;
+
+use super::{OperationContext, OperationRegistry};
+use crate::{
+    entities::Entity,
+    report::{WarningKind, Warnings},
+    unified::Operation,
+    ConversionError,
+};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("runAdminCommand", run_admin_command)
+        .register("runCommand", run_command);
+}
+
+fn run_admin_command(
+    op: &mut Operation,
+    context: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    insert_command_name(&mut op.arguments, context.command_name, context.warnings);
+    op.object = Entity::AdminDatabase.reference();
+    op.name = "runCommand".to_string();
+    Ok(())
+}
+
+fn run_command(op: &mut Operation, context: &OperationContext<'_>) -> Result<(), ConversionError> {
+    insert_command_name(&mut op.arguments, context.command_name, context.warnings);
+    Ok(())
+}
+
+/// Sets `commandName`, falling back to the first key of the command document when the legacy
+/// operation doesn't specify one.
+fn insert_command_name(
+    arguments: &mut Option<Document>,
+    command_name: Option<&str>,
+    warnings: &Warnings,
+) {
+    let arguments = arguments.get_or_insert_with(Document::new);
+    let command_name = command_name.map(String::from).or_else(|| {
+        arguments
+            .get_document("command")
+            .ok()
+            .and_then(|command| command.keys().next().cloned())
+    });
+    match command_name {
+        Some(command_name) => {
+            arguments.insert("commandName", command_name);
+        }
+        None => warnings.warn(WarningKind::MissingCommandName),
+    }
+}
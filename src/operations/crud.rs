@@ -0,0 +1,103 @@
+use bson::{doc, Bson, Document};
+
+use super::{required_argument, OperationContext, OperationRegistry};
+use crate::{report::WarningKind, unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("bulkWrite", bulk_write)
+        .register("findOneAndDelete", find_and_modify)
+        .register("findOneAndReplace", find_and_modify)
+        .register("findOneAndUpdate", find_and_modify);
+}
+
+/// Legacy requests name each write and list its arguments separately, whereas the unified format
+/// keys each request's arguments by the name of the write. The bulk write's `options` are
+/// top-level arguments in the unified format.
+fn bulk_write(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    required_argument(&op.name, &op.arguments, "requests")?;
+    let arguments = op.arguments.get_or_insert_with(Document::new);
+    if let Some(Bson::Document(options)) = arguments.remove("options") {
+        arguments.extend(options);
+    }
+    let requests =
+        arguments
+            .get_array_mut("requests")
+            .map_err(|_| ConversionError::InvalidArgument {
+                operation: op.name.clone(),
+                argument: "requests",
+                expected: "array",
+            })?;
+    for request in requests.iter_mut() {
+        let request = match request {
+            Bson::Document(request) => request,
+            _ => continue,
+        };
+        let name = match request.get_str("name") {
+            Ok(name) => name.to_string(),
+            // already in the unified form
+            Err(_) => continue,
+        };
+        let arguments = match request.remove("arguments") {
+            Some(Bson::Document(arguments)) => arguments,
+            _ => Document::new(),
+        };
+        *request = Document::new();
+        request.insert(name, arguments);
+    }
+    Ok(())
+}
+
+/// The arguments that only some of the findOneAndX operations take.
+static MODIFY_ARGUMENTS: &[&str] = &["arrayFilters", "returnDocument", "upsert"];
+
+/// Normalizes the arguments of the findOneAndX operations into the forms their unified schemas
+/// accept, dropping ones that don't apply to the operation.
+fn find_and_modify(
+    op: &mut Operation,
+    context: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    let arguments = match op.arguments {
+        Some(ref mut arguments) => arguments,
+        None => return Ok(()),
+    };
+
+    // the shell's spelling of returnDocument.
+    if let Some(Bson::Boolean(new)) = arguments.remove("returnNewDocument") {
+        arguments.insert("returnDocument", if new { "After" } else { "Before" });
+    }
+    if let Ok(return_document) = arguments.get_str("returnDocument") {
+        let normalized = ["Before", "After"]
+            .into_iter()
+            .find(|value| value.eq_ignore_ascii_case(return_document));
+        match normalized {
+            Some(normalized) => {
+                arguments.insert("returnDocument", normalized);
+            }
+            None => context.warnings.warn(WarningKind::UnexpectedValue {
+                field: "returnDocument".to_string(),
+                value: format!("{:?}", return_document),
+                expected: "\"Before\" or \"After\"",
+            }),
+        }
+    }
+    if let Ok(locale) = arguments.get_str("collation") {
+        let collation = doc! { "locale": locale };
+        arguments.insert("collation", collation);
+    }
+
+    for argument in MODIFY_ARGUMENTS {
+        let applies = match op.name.as_str() {
+            "findOneAndUpdate" => true,
+            "findOneAndReplace" => *argument != "arrayFilters",
+            _ => false,
+        };
+        if !applies && arguments.remove(*argument).is_some() {
+            context.warnings.warn(WarningKind::DroppedField {
+                field: format!("arguments.{}", argument),
+                reason: "it does not apply to the operation",
+            });
+        }
+    }
+    Ok(())
+}
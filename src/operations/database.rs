@@ -0,0 +1,27 @@
+use super::{OperationContext, OperationRegistry};
+use crate::{entities::Entity, unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    for name in ["createCollection", "dropCollection", "modifyCollection"] {
+        registry.register(name, collection_argument);
+    }
+}
+
+/// Refers to the test collection in a database operation's `collection` argument by its anchor.
+fn collection_argument(
+    op: &mut Operation,
+    context: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    if op.object != Entity::Database.reference() {
+        return Ok(());
+    }
+    if let Some(ref mut arguments) = op.arguments {
+        if let Ok(collection_name) = arguments.get_str("collection") {
+            let collection_name = context
+                .namespace
+                .collection_name_ref(collection_name.to_string());
+            arguments.insert("collection", collection_name);
+        }
+    }
+    Ok(())
+}
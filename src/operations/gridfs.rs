@@ -0,0 +1,18 @@
+use super::{OperationContext, OperationRegistry};
+use crate::{entities::Entity, normalize, unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("download", file_id_argument)
+        .register("delete", file_id_argument);
+}
+
+fn file_id_argument(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    if op.object != Entity::Bucket.reference() {
+        return Ok(());
+    }
+    if let Some(ref mut arguments) = op.arguments {
+        normalize::object_id(arguments, "id");
+    }
+    Ok(())
+}
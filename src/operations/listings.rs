@@ -0,0 +1,15 @@
+use super::{OperationContext, OperationRegistry};
+use crate::{unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("listCollectionObjects", list_objects)
+        .register("listDatabaseObjects", list_objects);
+}
+
+/// Some drivers' legacy runners distinguished listing documents from listing model objects,
+/// which the unified format doesn't.
+fn list_objects(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    op.name = op.name.replace("Objects", "s");
+    Ok(())
+}
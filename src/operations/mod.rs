@@ -0,0 +1,160 @@
+//! Conversions of individual legacy operations, keyed by the legacy operation's name.
+//!
+//! Each module registers the operations it's responsible for in the default
+//! [`OperationRegistry`], and callers can register their own converters on top of those.
+
+mod change_streams;
+mod commands;
+mod crud;
+mod database;
+mod gridfs;
+mod listings;
+mod sessions;
+mod test_runner;
+mod threads;
+mod transactions;
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use bson::{Bson, Document};
+
+use crate::{
+    report::Warnings,
+    unified::{Namespace, Operation},
+    ConversionError,
+};
+
+/// Converts a legacy operation into its unified form.
+pub trait OperationConverter: Send + Sync {
+    /// Converts `operation` in place. Its name, object and arguments have already been carried
+    /// over from the legacy operation, with the standard entities and session arguments
+    /// substituted in.
+    fn convert(
+        &self,
+        operation: &mut Operation,
+        context: &OperationContext<'_>,
+    ) -> Result<(), ConversionError>;
+}
+
+impl<F> OperationConverter for F
+where
+    F: Fn(&mut Operation, &OperationContext<'_>) -> Result<(), ConversionError> + Send + Sync,
+{
+    fn convert(
+        &self,
+        operation: &mut Operation,
+        context: &OperationContext<'_>,
+    ) -> Result<(), ConversionError> {
+        self(operation, context)
+    }
+}
+
+/// What an [`OperationConverter`] knows about the test the operation is part of.
+#[derive(Clone, Copy)]
+pub struct OperationContext<'a> {
+    pub namespace: Namespace<'a>,
+
+    /// The names of the threads the test starts, in the order they're started.
+    pub threads: &'a [String],
+
+    /// The registry the operation was looked up in, for converting nested operations.
+    pub operations: &'a OperationRegistry,
+
+    /// The legacy operation's `command_name`, if it had one.
+    pub command_name: Option<&'a str>,
+
+    /// Whether unrecognized operations are errors rather than passed through.
+    pub strict: bool,
+
+    /// Where to report anything the conversion can't carry over faithfully.
+    pub warnings: &'a Warnings,
+}
+
+/// The converters to use for each legacy operation name. Operations without a converter are
+/// carried over unchanged. Clones share their converters.
+#[derive(Clone)]
+pub struct OperationRegistry {
+    converters: BTreeMap<String, Arc<dyn OperationConverter>>,
+}
+
+impl OperationRegistry {
+    /// A registry without any converters.
+    pub fn empty() -> Self {
+        Self {
+            converters: BTreeMap::new(),
+        }
+    }
+
+    /// Registers the converter for operations named `name`, replacing any existing one.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        converter: impl OperationConverter + 'static,
+    ) -> &mut Self {
+        self.converters.insert(name.into(), Arc::new(converter));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn OperationConverter> {
+        self.converters.get(name).map(Arc::as_ref)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.converters.contains_key(name)
+    }
+}
+
+impl Default for OperationRegistry {
+    /// A registry with the converters for all of the legacy operations that need more than
+    /// carrying over.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        change_streams::register(&mut registry);
+        commands::register(&mut registry);
+        crud::register(&mut registry);
+        database::register(&mut registry);
+        gridfs::register(&mut registry);
+        listings::register(&mut registry);
+        sessions::register(&mut registry);
+        test_runner::register(&mut registry);
+        threads::register(&mut registry);
+        transactions::register(&mut registry);
+        registry
+    }
+}
+
+impl fmt::Debug for OperationRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.converters.keys()).finish()
+    }
+}
+
+/// Looks up an argument that the legacy operation can't be converted without.
+pub(crate) fn required_argument<'a>(
+    operation: &str,
+    arguments: &'a Option<Document>,
+    argument: &'static str,
+) -> Result<&'a Bson, ConversionError> {
+    arguments
+        .as_ref()
+        .and_then(|arguments| arguments.get(argument))
+        .ok_or_else(|| ConversionError::MissingArgument {
+            operation: operation.to_string(),
+            argument,
+        })
+}
+
+pub(crate) fn required_str<'a>(
+    operation: &str,
+    arguments: &'a Option<Document>,
+    argument: &'static str,
+) -> Result<&'a str, ConversionError> {
+    match required_argument(operation, arguments, argument)? {
+        Bson::String(value) => Ok(value.as_str()),
+        _ => Err(ConversionError::InvalidArgument {
+            operation: operation.to_string(),
+            argument,
+            expected: "string",
+        }),
+    }
+}
@@ -0,0 +1,18 @@
+use bson::doc;
+
+use super::{OperationContext, OperationRegistry};
+use crate::{entities::Entity, unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("assertSameLsidOnLastTwoCommands", lsid_assertion)
+        .register("assertDifferentLsidOnLastTwoCommands", lsid_assertion);
+}
+
+fn lsid_assertion(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    op.arguments = doc! {
+        "client": Entity::Client.reference(),
+    }
+    .into();
+    Ok(())
+}
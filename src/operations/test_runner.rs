@@ -0,0 +1,112 @@
+use bson::doc;
+
+use super::{required_argument, required_str, OperationContext, OperationRegistry};
+use crate::{
+    entities::Entity,
+    unified::{Operation, CONNECTION_CREATED, POOL_CLEARED, POOL_READY, SERVER_DESCRIPTION_CHANGED},
+    ConversionError,
+};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("waitForEvent", event_count)
+        .register("assertEventCount", event_count)
+        .register("recordPrimary", record_primary)
+        .register("waitForPrimaryChange", wait_for_primary_change)
+        .register("wait", wait)
+        .register("configureFailPoint", configure_fail_point);
+    for name in [
+        "assertCollectionExists",
+        "assertCollectionNotExists",
+        "assertIndexExists",
+        "assertIndexNotExists",
+    ] {
+        registry.register(name, namespace_assertion);
+    }
+}
+
+fn event_count(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    let event = match required_str(&op.name, &op.arguments, "event")? {
+        "ServerMarkedUnknownEvent" => doc! {
+            SERVER_DESCRIPTION_CHANGED: {
+                "newDescription": { "type": "Unknown" }
+            }
+        },
+        "PoolClearedEvent" => doc! {
+            POOL_CLEARED: { }
+        },
+        "PoolReadyEvent" => doc! { POOL_READY: { } },
+        "ConnectionCreatedEvent" => doc! { CONNECTION_CREATED: { } },
+        e => return Err(ConversionError::UnrecognizedEvent(e.to_string())),
+    };
+
+    op.arguments = doc! {
+        "client": Entity::Client.reference(),
+        "event": event,
+        "count": required_argument(&op.name, &op.arguments, "count")?
+    }
+    .into();
+    Ok(())
+}
+
+fn record_primary(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    op.arguments = doc! {
+        "client": Entity::Client.reference(),
+        "id": Entity::TopologyDescription.definition(),
+    }
+    .into();
+    op.name = "recordTopologyDescription".to_string();
+    Ok(())
+}
+
+fn wait_for_primary_change(
+    op: &mut Operation,
+    _: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    let mut new_arguments = doc! {
+        "client": Entity::Client.reference(),
+        "priorTopologyDescription": Entity::TopologyDescription.reference(),
+    };
+    if let Some(timeout) = op.arguments.as_ref().and_then(|a| a.get("timeoutMS")) {
+        new_arguments.insert("timeoutMS", timeout);
+    }
+    op.arguments = Some(new_arguments);
+    Ok(())
+}
+
+fn namespace_assertion(
+    op: &mut Operation,
+    context: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    let database_name = required_str(&op.name, &op.arguments, "database")?;
+    let collection_name = required_str(&op.name, &op.arguments, "collection")?;
+    let mut new_arguments = doc! {
+        "databaseName": context.namespace.database_name_ref(database_name.to_string()),
+        "collectionName": context.namespace.collection_name_ref(collection_name.to_string()),
+    };
+    if let Some(Ok(index_name)) = op.arguments.as_ref().map(|a| a.get_str("index")) {
+        new_arguments.insert("indexName", index_name);
+    }
+    op.arguments = Some(new_arguments);
+    Ok(())
+}
+
+fn wait(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    let ms = required_argument(&op.name, &op.arguments, "ms")?.clone();
+    op.object = "testRunner".to_string();
+    op.arguments = doc! { "ms": ms }.into();
+    Ok(())
+}
+
+fn configure_fail_point(
+    op: &mut Operation,
+    _: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    required_argument(&op.name, &op.arguments, "failPoint")?;
+    op.object = "testRunner".to_string();
+    op.name = "failPoint".to_string();
+    op.arguments
+        .get_or_insert_with(bson::Document::new)
+        .insert("client", Entity::SetupClient.reference());
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use bson::doc;
+
+use super::{required_argument, required_str, OperationContext, OperationRegistry};
+use crate::{
+    crud_v2,
+    entities::Entity,
+    unified::{CreateEntity, Operation},
+    ConversionError,
+};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("startThread", start_thread)
+        .register("runOnThread", run_on_thread)
+        .register("waitForThread", wait_for_thread);
+}
+
+fn start_thread(op: &mut Operation, context: &OperationContext<'_>) -> Result<(), ConversionError> {
+    let thread_name = required_str(&op.name, &op.arguments, "name")?;
+    let thread_number = thread_number(context.threads, thread_name)?;
+    let thread_entity = CreateEntity::Thread {
+        id: Entity::Thread(thread_number).definition(),
+    };
+    op.name = "createEntities".to_string();
+    op.object = "testRunner".to_string();
+    op.arguments = doc! {
+        "entities": [
+            bson::to_bson(&thread_entity)?
+        ]
+    }
+    .into();
+    Ok(())
+}
+
+fn run_on_thread(op: &mut Operation, context: &OperationContext<'_>) -> Result<(), ConversionError> {
+    let thread_name = required_str(&op.name, &op.arguments, "name")?;
+    let thread_number = thread_number(context.threads, thread_name)?;
+
+    let old_operation: crud_v2::Operation =
+        bson::from_bson(required_argument(&op.name, &op.arguments, "operation")?.clone())?;
+    let new_op = Operation::from_crud_v2(old_operation, context)?;
+
+    op.arguments = doc! {
+        "thread": Entity::Thread(thread_number).reference(),
+        "operation": bson::to_bson(&new_op)?
+    }
+    .into();
+    Ok(())
+}
+
+fn wait_for_thread(
+    op: &mut Operation,
+    context: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    let thread_name = required_str(&op.name, &op.arguments, "name")?;
+    let thread_number = thread_number(context.threads, thread_name)?;
+    op.arguments = doc! {
+        "thread": Entity::Thread(thread_number).reference()
+    }
+    .into();
+    Ok(())
+}
+
+fn thread_number(threads: &[String], name: &str) -> Result<usize, ConversionError> {
+    threads
+        .iter()
+        .position(|thread| thread == name)
+        .ok_or_else(|| ConversionError::UnknownThread(name.to_string()))
+}
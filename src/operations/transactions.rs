@@ -0,0 +1,64 @@
+use bson::{Bson, Document};
+
+use super::{required_argument, OperationContext, OperationRegistry};
+use crate::{crud_v2, normalize, unified::Operation, ConversionError};
+
+pub(super) fn register(registry: &mut OperationRegistry) {
+    registry
+        .register("startTransaction", start_transaction)
+        .register("withTransaction", with_transaction);
+}
+
+fn start_transaction(op: &mut Operation, _: &OperationContext<'_>) -> Result<(), ConversionError> {
+    flatten_options(op);
+    if op.arguments.as_ref().is_some_and(Document::is_empty) {
+        op.arguments = None;
+    }
+    Ok(())
+}
+
+fn with_transaction(
+    op: &mut Operation,
+    context: &OperationContext<'_>,
+) -> Result<(), ConversionError> {
+    // the legacy callback is a document listing its operations, which are converted like any
+    // others, so fail points and nested callbacks in them are too.
+    let callback = match required_argument(&op.name, &op.arguments, "callback")? {
+        Bson::Document(callback) => callback
+            .get_array("operations")
+            .cloned()
+            .unwrap_or_default(),
+        _ => {
+            return Err(ConversionError::InvalidArgument {
+                operation: op.name.clone(),
+                argument: "callback",
+                expected: "document",
+            })
+        }
+    };
+    let callback = callback
+        .into_iter()
+        .map(|old_op| {
+            let old_op: crud_v2::Operation = bson::from_bson(old_op)?;
+            Ok(bson::to_bson(&Operation::from_crud_v2(old_op, context)?)?)
+        })
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+    op.arguments
+        .get_or_insert_with(Document::new)
+        .insert("callback", callback);
+    flatten_options(op);
+    Ok(())
+}
+
+/// Moves the transaction options nested in an operation's `options` argument up into its
+/// arguments, where the unified format takes them.
+fn flatten_options(op: &mut Operation) {
+    if let Some(Bson::Document(options)) = op.arguments.as_mut().and_then(|a| a.remove("options")) {
+        let arguments = op.arguments.get_or_insert_with(Document::new);
+        arguments.extend(options);
+        // the options were nested out of reach of the normalization of the legacy arguments.
+        normalize::read_preference(arguments);
+        normalize::timeout_ms(arguments);
+        normalize::max_commit_time_ms(arguments);
+    }
+}
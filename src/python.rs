@@ -0,0 +1,69 @@
+//! Python bindings, so that the converter can be used from migration scripts.
+//!
+//! The crate is only an rlib by default, so build the extension module as a cdylib with
+//! `cargo rustc --release --lib --features python --crate-type cdylib`, renaming the library to
+//! `v2_to_unified.so` (or `.pyd` on Windows).
+
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use serde_json::{Map, Value};
+
+use crate::ConvertOptions;
+
+/// Converts the YAML contents of a legacy test file into a unified test file. Options are
+/// passed as keyword arguments named after the command line flags, e.g.
+/// `convert(text, hoist_entities=True)`.
+#[pyfunction]
+#[pyo3(signature = (text, **options))]
+fn convert(text: &str, options: Option<&Bound<'_, PyDict>>) -> PyResult<String> {
+    let options = match options {
+        Some(options) => parse_options(options)?,
+        None => ConvertOptions::default(),
+    };
+    crate::convert_str(text, &options)
+        .map(|(output, _)| output)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Converts Python keyword arguments into options via their camelCase serde names.
+fn parse_options(options: &Bound<'_, PyDict>) -> PyResult<ConvertOptions> {
+    let mut map = Map::new();
+    for (key, value) in options.iter() {
+        let key: String = key.extract()?;
+        let value = if let Ok(value) = value.extract::<bool>() {
+            Value::Bool(value)
+        } else if let Ok(value) = value.extract::<String>() {
+            Value::String(value)
+        } else if let Ok(values) = value.extract::<Vec<String>>() {
+            values.into_iter().map(Value::String).collect()
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "option {} must be a bool, a str or a list of str",
+                key
+            )));
+        };
+        map.insert(camel_case(&key), value);
+    }
+    serde_json::from_value(Value::Object(map)).map_err(|e| PyTypeError::new_err(e.to_string()))
+}
+
+fn camel_case(name: &str) -> String {
+    let mut words = name.split('_');
+    let mut camel = words.next().unwrap_or_default().to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+#[pymodule]
+fn v2_to_unified(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(convert, module)?)
+}
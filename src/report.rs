@@ -0,0 +1,328 @@
+//! What a conversion did that callers may want to check, such as the legacy fields it had to
+//! drop.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use bson::{Bson, Document};
+use serde::Serialize;
+
+use crate::Location;
+
+/// A summary of the conversion of a single legacy test file.
+#[derive(Debug, Default)]
+pub struct ConversionReport {
+    pub warnings: Vec<Warning>,
+
+    /// The placeholders the converter serialized and what they were replaced with in the output,
+    /// e.g. `xENTITY_client_REFERENCE` and `*client`.
+    pub substitutions: BTreeMap<String, String>,
+
+    /// The schema version the converted file declares.
+    pub schema_version: String,
+
+    /// The lowest schema version that supports every feature the converted file uses.
+    pub required_schema_version: String,
+
+    /// The description of each legacy test, along with the description of the unified test it
+    /// was converted to.
+    pub tests: Vec<(String, String)>,
+
+    /// The unified test runner features the converted file uses.
+    pub capabilities: Capabilities,
+}
+
+/// The unified test runner features a converted file uses, so that driver teams can check their
+/// runner supports them before adopting the file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// The operations on the `testRunner` object, e.g. `failPoint`.
+    pub test_runner_operations: BTreeSet<String>,
+
+    /// The special operators, e.g. `$$unsetOrMatches`.
+    pub special_operators: BTreeSet<String>,
+
+    /// The events that clients observe, expect, or wait for, e.g. `commandStartedEvent`.
+    pub events: BTreeSet<String>,
+
+    /// The lowest schema version that supports every feature the file uses.
+    pub schema_version: String,
+}
+
+impl Capabilities {
+    /// The capabilities the given converted file needs.
+    pub(crate) fn of(file: &Bson) -> Self {
+        let mut capabilities = Self {
+            schema_version: required_schema_version(file),
+            ..Default::default()
+        };
+        capabilities.add(file);
+        capabilities
+    }
+
+    fn add(&mut self, value: &Bson) {
+        let doc = match value {
+            Bson::Document(doc) => doc,
+            Bson::Array(values) => return values.iter().for_each(|value| self.add(value)),
+            _ => return,
+        };
+        if let (Ok("testRunner"), Ok(name)) = (doc.get_str("object"), doc.get_str("name")) {
+            self.test_runner_operations.insert(name.to_string());
+            if let Ok(event) = doc
+                .get_document("arguments")
+                .and_then(|arguments| arguments.get_document("event"))
+            {
+                self.add_events(event);
+            }
+        }
+        if let Ok(observed) = doc.get_array("observeEvents") {
+            self.events
+                .extend(observed.iter().filter_map(Bson::as_str).map(String::from));
+        }
+        for expected in doc.get_array("expectEvents").into_iter().flatten() {
+            let events = expected.as_document().and_then(|e| e.get_array("events").ok());
+            for event in events.into_iter().flatten().filter_map(Bson::as_document) {
+                self.add_events(event);
+            }
+        }
+        for (key, value) in doc {
+            if key.starts_with("$$") {
+                self.special_operators.insert(key.clone());
+            }
+            self.add(value);
+        }
+    }
+
+    fn add_events(&mut self, events: &Document) {
+        self.events.extend(events.keys().cloned());
+    }
+}
+
+/// Something the converter couldn't carry over faithfully, which may need a human to check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub location: Location,
+    pub kind: WarningKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A legacy field that was left out of the converted file.
+    DroppedField { field: String, reason: &'static str },
+
+    /// An operation the converter doesn't recognize, which was passed through unchanged.
+    UnknownOperation(String),
+
+    /// A problem with a test's server version bounds.
+    ServerVersion(String),
+
+    /// A topology name that's deprecated in the unified format.
+    DeprecatedTopology(String),
+
+    /// An operation that says it doesn't error but has an error result. It's converted to
+    /// expect an error.
+    ContradictoryError(String),
+
+    /// A legacy value of the wrong type that was converted to the type the unified format
+    /// expects, e.g. a URI option given as the string `"true"`.
+    CoercedValue {
+        field: String,
+        value: String,
+        expected: &'static str,
+    },
+
+    /// A legacy value that isn't one the field takes, which was carried over unchanged, e.g. a
+    /// `returnDocument` other than `"Before"` or `"After"`.
+    UnexpectedValue {
+        field: String,
+        value: String,
+        expected: &'static str,
+    },
+
+    /// A `runCommand` operation whose `commandName` couldn't be determined.
+    MissingCommandName,
+
+    /// The converted file uses features that its declared schema version doesn't support.
+    SchemaVersion { declared: String, required: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.location, self.kind)
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DroppedField { field, reason } => write!(f, "dropping {}, {}", field, reason),
+            Self::UnknownOperation(name) => write!(
+                f,
+                "unrecognized operation {}, passing it through unchanged",
+                name
+            ),
+            Self::ServerVersion(problem) => write!(f, "{}", problem),
+            Self::DeprecatedTopology(topology) => write!(
+                f,
+                "{} is deprecated in the unified format, consider --normalize-topologies",
+                topology
+            ),
+            Self::ContradictoryError(operation) => write!(
+                f,
+                "{} specifies error: false but has an error result, expecting an error",
+                operation
+            ),
+            Self::CoercedValue {
+                field,
+                value,
+                expected,
+            } => write!(f, "converting {} from {} to {}", field, value, expected),
+            Self::UnexpectedValue {
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "{} is {}, not {}, passing it through unchanged",
+                field, value, expected
+            ),
+            Self::MissingCommandName => {
+                write!(f, "unable to determine commandName for runCommand")
+            }
+            Self::SchemaVersion { declared, required } => write!(
+                f,
+                "the converted file declares schema version {} but uses features from {}",
+                declared, required
+            ),
+        }
+    }
+}
+
+impl From<WarningKind> for Warning {
+    fn from(kind: WarningKind) -> Self {
+        Self {
+            location: Location::default(),
+            kind,
+        }
+    }
+}
+
+/// Collects the warnings raised while converting a file. Warnings are raised without a
+/// location, which is filled in as they propagate up to the test and file being converted.
+#[derive(Debug, Default)]
+pub struct Warnings(RefCell<Vec<Warning>>);
+
+impl Warnings {
+    pub fn warn(&self, kind: WarningKind) {
+        self.0.borrow_mut().push(kind.into());
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Updates the locations of the warnings raised since there were `start` of them.
+    pub(crate) fn locate(&self, start: usize, update: impl Fn(&mut Location)) {
+        for warning in self.0.borrow_mut()[start..].iter_mut() {
+            update(&mut warning.location);
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> Vec<Warning> {
+        self.0.into_inner()
+    }
+}
+
+/// The features the converter emits that were added to the unified format after 1.0, with
+/// the schema version that added them.
+const VERSIONED_FEATURES: &[(&str, &str, (u32, u32))] = &[
+    ("eventType", "cmap", (1, 3)),
+    ("serverless", "", (1, 4)),
+    ("observeSensitiveCommands", "", (1, 5)),
+    ("name", "createEntities", (1, 9)),
+    ("timeoutMS", "", (1, 9)),
+    ("eventType", "sdam", (1, 10)),
+    ("name", "runOnThread", (1, 10)),
+    ("name", "waitForThread", (1, 10)),
+    ("errorResponse", "", (1, 12)),
+];
+
+/// The topologies `runOnRequirements` can list that were added after 1.0, with the schema
+/// version that added them.
+const VERSIONED_TOPOLOGIES: &[(&str, (u32, u32))] = &[("load-balanced", (1, 3))];
+
+/// The schema version that added the client-side operations timeout (CSOT) fields.
+pub(crate) static CSOT_SCHEMA_VERSION: &str = "1.9";
+
+/// Whether the given converted file uses any of the CSOT fields.
+pub(crate) fn uses_csot(value: &Bson) -> bool {
+    match value {
+        Bson::Document(doc) => doc
+            .iter()
+            .any(|(key, value)| key == "timeoutMS" || uses_csot(value)),
+        Bson::Array(values) => values.iter().any(uses_csot),
+        _ => false,
+    }
+}
+
+/// The lowest schema version that supports every feature used in the given converted file.
+pub(crate) fn required_schema_version(file: &Bson) -> String {
+    let (major, minor) = required_version(file);
+    format!("{}.{}", major, minor)
+}
+
+fn required_version(value: &Bson) -> (u32, u32) {
+    match value {
+        Bson::Document(doc) => doc
+            .iter()
+            .map(|(key, value)| {
+                let feature = VERSIONED_FEATURES
+                    .iter()
+                    .filter(|(feature_key, feature_value, _)| {
+                        feature_key == key
+                            && (feature_value.is_empty() || value.as_str() == Some(feature_value))
+                    })
+                    .map(|(_, _, version)| *version)
+                    .max()
+                    .unwrap_or((1, 0));
+                feature
+                    .max(topologies_version(key, value))
+                    .max(required_version(value))
+            })
+            .max()
+            .unwrap_or((1, 0)),
+        Bson::Array(values) => values.iter().map(required_version).max().unwrap_or((1, 0)),
+        _ => (1, 0),
+    }
+}
+
+fn topologies_version(key: &str, value: &Bson) -> (u32, u32) {
+    let topologies = match value {
+        Bson::Array(topologies) if key == "topologies" => topologies,
+        _ => return (1, 0),
+    };
+    topologies
+        .iter()
+        .filter_map(|topology| {
+            VERSIONED_TOPOLOGIES
+                .iter()
+                .find(|(name, _)| topology.as_str() == Some(name))
+                .map(|(_, version)| *version)
+        })
+        .max()
+        .unwrap_or((1, 0))
+}
+
+/// Whether schema version `a` is older than `b`. Unparseable versions compare as 1.0.
+pub(crate) fn older_than(a: &str, b: &str) -> bool {
+    fn parse(version: &str) -> (u32, u32) {
+        let mut parts = version.split('.').map(|part| part.parse().unwrap_or(0));
+        (parts.next().unwrap_or(1), parts.next().unwrap_or(0))
+    }
+    parse(a) < parse(b)
+}
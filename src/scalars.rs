@@ -0,0 +1,77 @@
+//! Quoting of strings that YAML parsers could read back as something other than a string.
+//!
+//! The YAML emitter quotes strings that look like YAML 1.2 booleans, nulls and numbers, but not
+//! every form that YAML 1.1 parsers, like the ones the specifications repository converts files
+//! to JSON with, read as numbers or timestamps.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_yaml::Value;
+
+/// Matches the plain scalars that YAML 1.1 or 1.2 parsers read as numbers or timestamps and that
+/// the emitter might leave unquoted, e.g. `0o17`, `1_000`, `+.inf` and `2021-01-01`.
+static AMBIGUOUS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)^(?:
+            [-+]?(?:
+                0b[01_]+
+                | 0o[0-7_]+
+                | 0x[0-9a-fA-F_]+
+                | [0-9][0-9_]*
+                | [0-9][0-9_]*\.[0-9_]*(?:[eE][-+]?[0-9]+)?
+                | \.[0-9][0-9_]*(?:[eE][-+]?[0-9]+)?
+                | \.(?:inf|Inf|INF)
+            )
+            | \.(?:nan|NaN|NAN)
+            | [0-9]{4}-[0-9]{1,2}-[0-9]{1,2}
+        )$",
+    )
+    .unwrap()
+});
+
+/// The placeholder that `quote_ambiguous` serializes in place of an ambiguous string.
+fn placeholder(i: usize) -> String {
+    format!("xQUOTED_STRING_{}", i)
+}
+
+/// A string as a double-quoted YAML scalar. JSON strings are valid double-quoted YAML scalars.
+fn quoted(s: &str) -> String {
+    serde_json::to_string(s).expect("strings serialize to JSON")
+}
+
+/// A string as a YAML scalar that reads back as the same string, for values that are written
+/// into a serialized file directly.
+pub(crate) fn scalar(s: &str) -> String {
+    let emitted = serde_yaml::to_string(s).unwrap_or_default();
+    let emitted = emitted.trim_start_matches("---").trim();
+    if emitted == s && AMBIGUOUS.is_match(s) {
+        quoted(s)
+    } else {
+        emitted.to_string()
+    }
+}
+
+/// Replaces the ambiguous strings in a value with placeholders, returning the placeholders along
+/// with the quoted scalars to replace them with once the value has been serialized.
+pub(crate) fn quote_ambiguous(value: &mut Value) -> Vec<(String, String)> {
+    let mut replacements = Vec::new();
+    replace_ambiguous(value, &mut replacements);
+    replacements
+}
+
+fn replace_ambiguous(value: &mut Value, replacements: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(s) if AMBIGUOUS.is_match(s) => {
+            let placeholder = placeholder(replacements.len());
+            replacements.push((placeholder.clone(), quoted(s)));
+            *s = placeholder;
+        }
+        Value::Mapping(mapping) => mapping
+            .iter_mut()
+            .for_each(|(_, value)| replace_ambiguous(value, replacements)),
+        Value::Sequence(values) => values
+            .iter_mut()
+            .for_each(|value| replace_ambiguous(value, replacements)),
+        _ => {}
+    }
+}
@@ -0,0 +1,60 @@
+//! Validation of converted files against the unified test format's JSON Schema.
+//!
+//! The bundled schema is a hand-written subset of the format covering the parts converted files
+//! can use, up to the newest schema version the converter emits features from, rather than one
+//! of the specifications' own `schema-1.x.json` files. Files are checked against it whatever
+//! `schemaVersion` they declare, so it catches structural mistakes but not a file using a
+//! feature newer than its declared version.
+
+use std::fmt;
+
+use jsonschema::Validator;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+
+use crate::ConversionError;
+
+static SCHEMA: &str = include_str!("../schemas/unified-test-format.json");
+
+/// The bundled schema, compiled once and shared by every conversion.
+static VALIDATOR: Lazy<Validator> = Lazy::new(|| {
+    let schema: Value = serde_json::from_str(SCHEMA).expect("bundled schema is valid JSON");
+    jsonschema::validator_for(&schema).expect("bundled schema is valid")
+});
+
+/// A part of a converted file that the schema doesn't allow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// A JSON pointer to the offending value, e.g. `/tests/0/operations/1`.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            self.pointer.as_str()
+        };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+/// Checks a converted file against the unified test format's schema.
+pub(crate) fn validate(yaml: &str) -> Result<(), ConversionError> {
+    let file: Value = serde_yaml::from_str(yaml).map_err(ConversionError::Malformed)?;
+
+    let violations: Vec<_> = VALIDATOR
+        .iter_errors(&file)
+        .map(|error| SchemaViolation {
+            pointer: error.instance_path.to_string(),
+            message: error.to_string(),
+        })
+        .collect();
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ConversionError::SchemaViolations(violations))
+    }
+}
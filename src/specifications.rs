@@ -0,0 +1,95 @@
+//! The layout of the MongoDB specifications repository, for converting the legacy tests of every
+//! spec family in one go.
+
+use std::path::{Path, PathBuf};
+
+use crate::ConvertOptions;
+
+/// A spec family whose legacy tests the converter can convert.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpecFamily {
+    /// The family's directory under `source/`, e.g. `transactions`.
+    pub name: &'static str,
+
+    /// The directory of the family's legacy tests, relative to the family's directory.
+    pub legacy_dir: &'static str,
+
+    /// The database name the family's files use when they don't specify a `database_name`.
+    pub default_database_name: &'static str,
+}
+
+/// The spec families with legacy tests in the v2 format.
+pub static SPEC_FAMILIES: &[SpecFamily] = &[
+    SpecFamily {
+        name: "server-discovery-and-monitoring",
+        legacy_dir: "tests/integration",
+        default_database_name: "sdam-tests",
+    },
+    SpecFamily {
+        name: "change-streams",
+        legacy_dir: "tests/legacy",
+        default_database_name: "change-stream-tests",
+    },
+    SpecFamily {
+        name: "client-side-encryption",
+        legacy_dir: "tests/legacy",
+        default_database_name: "default",
+    },
+    SpecFamily {
+        name: "crud",
+        legacy_dir: "tests/v2",
+        default_database_name: "crud-tests",
+    },
+    SpecFamily {
+        name: "retryable-reads",
+        legacy_dir: "tests/legacy",
+        default_database_name: "retryable-reads-tests",
+    },
+    SpecFamily {
+        name: "retryable-writes",
+        legacy_dir: "tests/legacy",
+        default_database_name: "retryable-writes-tests",
+    },
+    SpecFamily {
+        name: "sessions",
+        legacy_dir: "tests/legacy",
+        default_database_name: "session-tests",
+    },
+    SpecFamily {
+        name: "transactions",
+        legacy_dir: "tests/legacy",
+        default_database_name: "transaction-tests",
+    },
+    SpecFamily {
+        name: "transactions-convenient-api",
+        legacy_dir: "tests/legacy",
+        default_database_name: "withTransaction-tests",
+    },
+];
+
+impl SpecFamily {
+    /// The family's directory in the specifications repository rooted at `root`.
+    pub fn dir(&self, root: &Path) -> PathBuf {
+        root.join("source").join(self.name)
+    }
+
+    /// The directory containing the family's legacy tests.
+    pub fn legacy_path(&self, root: &Path) -> PathBuf {
+        self.dir(root).join(self.legacy_dir)
+    }
+
+    /// The directory the family's converted tests belong in.
+    pub fn unified_path(&self, root: &Path) -> PathBuf {
+        self.dir(root).join("tests").join("unified")
+    }
+
+    /// The options this family's files should be converted with, starting from the ones given
+    /// for the whole repository. Each family gets its own, so none of a family's options carry
+    /// over to the next.
+    pub fn options(&self, base: &ConvertOptions) -> ConvertOptions {
+        ConvertOptions {
+            default_database_name: self.default_database_name.to_string(),
+            ..base.clone()
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Splitting legacy test files into one file per test, for spec families that prefer smaller
+//! unified files than the legacy ones that bundle many loosely related tests.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::ConversionError;
+
+/// Splits the YAML contents of a legacy test file into one legacy file per test, each with the
+/// original file's data and other top-level fields, so that each converts to a unified file
+/// with its own `createEntities` and `initialData`. Each file is returned along with a slug of
+/// its test's description that's unique among the returned files.
+pub fn split_tests(input: &str) -> Result<Vec<(String, String)>, ConversionError> {
+    let file: Mapping =
+        serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(input))?;
+    let tests = match file.get(&Value::from("tests")) {
+        Some(Value::Sequence(tests)) => tests.clone(),
+        _ => Vec::new(),
+    };
+
+    let mut slugs: Vec<String> = Vec::new();
+    let mut files = Vec::new();
+    for (i, test) in tests.into_iter().enumerate() {
+        let description = test.get("description").and_then(Value::as_str);
+        let mut slug = description.map(slug).unwrap_or_default();
+        if slug.is_empty() {
+            slug = format!("test-{}", i);
+        }
+        let base = slug.clone();
+        for n in 2.. {
+            if !slugs.contains(&slug) {
+                break;
+            }
+            slug = format!("{}-{}", base, n);
+        }
+
+        let mut split = file.clone();
+        split.insert("tests".into(), Value::Sequence(vec![test]));
+        files.push((
+            slug.clone(),
+            serde_yaml::to_string(&split).map_err(ConversionError::Emit)?,
+        ));
+        slugs.push(slug);
+    }
+    Ok(files)
+}
+
+/// A file name friendly version of a test description, e.g. `insert-one-with-a-write-error` for
+/// "InsertOne with a write error".
+pub fn slug(description: &str) -> String {
+    description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
@@ -0,0 +1,160 @@
+//! Analysis of a corpus of legacy test files, for deciding which spec families are worth
+//! converting next and what the converter still needs to support.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{
+    convert_str,
+    crud_v2,
+    ConversionError,
+    ConvertOptions,
+    WarningKind,
+};
+
+/// Counts of the constructs used across a set of legacy test files.
+#[derive(Debug, Default)]
+pub struct CorpusStats {
+    pub files: usize,
+    pub tests: usize,
+
+    /// How many times each operation is used.
+    pub operations: BTreeMap<String, usize>,
+
+    /// How many times each event is expected, by its unified name.
+    pub events: BTreeMap<String, usize>,
+
+    /// How many `runOn` requirements list each topology.
+    pub topologies: BTreeMap<String, usize>,
+
+    pub tests_with_fail_points: usize,
+    pub tests_with_threads: usize,
+    pub tests_with_sessions: usize,
+
+    /// Why files couldn't be converted or were converted with unrecognized operations, and how
+    /// many files each reason applies to.
+    pub unsupported: BTreeMap<String, usize>,
+
+    /// The files that couldn't be parsed, along with the parse error.
+    pub unparseable: Vec<(String, String)>,
+}
+
+impl CorpusStats {
+    /// Adds the legacy test file with the given name and YAML contents to the counts.
+    pub fn add_str(&mut self, file_name: &str, input: &str) {
+        let file = match serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(input))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                let error = ConversionError::from(e);
+                self.unparseable.push((file_name.to_string(), error.to_string()));
+                return;
+            }
+        };
+        self.add(&file);
+
+        let options = ConvertOptions::builder().description(file_name).build();
+        match convert_str(input, &options) {
+            Ok((_, report)) => {
+                let unknown_operations: BTreeSet<_> = report
+                    .warnings
+                    .into_iter()
+                    .filter_map(|warning| match warning.kind {
+                        WarningKind::UnknownOperation(name) => Some(name),
+                        _ => None,
+                    })
+                    .collect();
+                for name in unknown_operations {
+                    *self
+                        .unsupported
+                        .entry(format!("unrecognized operation {}", name))
+                        .or_default() += 1;
+                }
+            }
+            Err(ConversionError::Context { error, .. }) => {
+                *self.unsupported.entry(error.to_string()).or_default() += 1
+            }
+            Err(error) => *self.unsupported.entry(error.to_string()).or_default() += 1,
+        }
+    }
+
+    /// Adds a parsed legacy test file to the counts.
+    pub fn add(&mut self, file: &crud_v2::TestFile) {
+        self.files += 1;
+        self.tests += file.tests.len();
+        let run_on = file
+            .run_on
+            .iter()
+            .flatten()
+            .chain(file.tests.iter().flat_map(|test| test.run_on.iter().flatten()));
+        for topology in run_on
+            .flat_map(|run_on| run_on.topology.iter().flatten())
+            .chain(file.tests.iter().flat_map(|test| test.topology.iter().flatten()))
+        {
+            *self.topologies.entry(topology.clone()).or_default() += 1;
+        }
+
+        for test in file.tests.iter() {
+            for op in test.operations.iter() {
+                *self.operations.entry(op.name.clone()).or_default() += 1;
+            }
+            for event in test.expectations.iter().flatten() {
+                *self.events.entry(event.unified_name().to_string()).or_default() += 1;
+            }
+            if test.fail_point.is_some()
+                || test
+                    .operations
+                    .iter()
+                    .any(|op| matches!(op.name.as_str(), "configureFailPoint" | "targetedFailPoint"))
+            {
+                self.tests_with_fail_points += 1;
+            }
+            if !test.thread_names().is_empty() {
+                self.tests_with_threads += 1;
+            }
+            if !test.session_names().is_empty() {
+                self.tests_with_sessions += 1;
+            }
+        }
+    }
+}
+
+impl fmt::Display for CorpusStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} files, {} tests", self.files, self.tests)?;
+        writeln!(f, "  {} tests with fail points", self.tests_with_fail_points)?;
+        writeln!(f, "  {} tests with threads", self.tests_with_threads)?;
+        writeln!(f, "  {} tests with sessions", self.tests_with_sessions)?;
+        write_counts(f, "operations", &self.operations)?;
+        write_counts(f, "expected events", &self.events)?;
+        write_counts(f, "topologies", &self.topologies)?;
+        write_counts(f, "unsupported (files)", &self.unsupported)?;
+        if !self.unparseable.is_empty() {
+            writeln!(f, "\nunparseable files:")?;
+            for (file, error) in self.unparseable.iter() {
+                writeln!(f, "  {}: {}", file, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a section of counts, most frequent first.
+fn write_counts(
+    f: &mut fmt::Formatter<'_>,
+    heading: &str,
+    counts: &BTreeMap<String, usize>,
+) -> fmt::Result {
+    if counts.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "\n{}:", heading)?;
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    for (name, count) in counts {
+        writeln!(f, "  {:>6}  {}", count, name)?;
+    }
+    Ok(())
+}
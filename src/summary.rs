@@ -0,0 +1,170 @@
+//! A Markdown summary of a batch conversion, for the description of the pull request that lands
+//! the converted tests, and a mapping from legacy tests to the unified tests they became.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crud_v2,
+    report::{self, Capabilities},
+    stats::CorpusStats,
+    ConversionReport,
+    WarningKind,
+};
+
+/// What a batch conversion converted, approximated, and couldn't convert.
+#[derive(Debug, Default)]
+pub struct MigrationSummary {
+    /// The converted files and how many tests each has, in the order they were converted.
+    pub converted: Vec<(String, usize)>,
+
+    /// Counts of the operations, events, and topologies the converted files use.
+    pub stats: CorpusStats,
+
+    /// What the converter dropped or approximated, and how many times.
+    pub approximations: BTreeMap<String, usize>,
+
+    /// The files that weren't converted, along with why.
+    pub skipped: Vec<(String, String)>,
+
+    /// Which unified test each converted legacy test became.
+    pub tests: Vec<TestMapping>,
+
+    /// The unified test runner features each converted file uses, keyed by the file.
+    pub capabilities: BTreeMap<String, Capabilities>,
+}
+
+/// Where a legacy test ended up, so that skip lists and flaky-test trackers that refer to tests
+/// by their legacy descriptions can be updated.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestMapping {
+    pub legacy_file: String,
+    pub legacy_description: String,
+    pub unified_file: String,
+    pub unified_description: String,
+}
+
+impl MigrationSummary {
+    /// Adds a converted file, given the legacy file's name and contents, the converted file's
+    /// name, and the report of its conversion.
+    pub fn add_converted(
+        &mut self,
+        legacy_file: &str,
+        input: &str,
+        unified_file: &str,
+        report: &ConversionReport,
+    ) {
+        let tests = match serde_yaml::from_str::<crud_v2::TestFile>(input) {
+            Ok(legacy) => {
+                self.stats.add(&legacy);
+                legacy.tests.len()
+            }
+            Err(_) => 0,
+        };
+        self.converted.push((unified_file.to_string(), tests));
+        self.tests.extend(report.tests.iter().map(|(legacy, unified)| TestMapping {
+            legacy_file: legacy_file.to_string(),
+            legacy_description: legacy.clone(),
+            unified_file: unified_file.to_string(),
+            unified_description: unified.clone(),
+        }));
+        self.capabilities
+            .insert(unified_file.to_string(), report.capabilities.clone());
+        for warning in report.warnings.iter() {
+            let approximation = match warning.kind {
+                WarningKind::ServerVersion(_) | WarningKind::SchemaVersion { .. } => continue,
+                WarningKind::DroppedField { ref field, reason } => {
+                    format!("dropped `{}`, {}", field, reason)
+                }
+                ref kind => kind.to_string(),
+            };
+            *self.approximations.entry(approximation).or_default() += 1;
+        }
+    }
+
+    /// Adds a file that wasn't converted.
+    pub fn add_skipped(&mut self, file: impl Into<String>, reason: impl fmt::Display) {
+        self.skipped.push((file.into(), reason.to_string()));
+    }
+}
+
+impl fmt::Display for MigrationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tests: usize = self.converted.iter().map(|(_, tests)| tests).sum();
+        writeln!(
+            f,
+            "Converted {} legacy test files ({} tests) to the unified test format.",
+            self.converted.len(),
+            tests
+        )?;
+
+        if !self.converted.is_empty() {
+            writeln!(f, "\n### Converted files\n")?;
+            writeln!(f, "| File | Tests |")?;
+            writeln!(f, "| --- | ---: |")?;
+            for (file, tests) in self.converted.iter() {
+                writeln!(f, "| `{}` | {} |", file, tests)?;
+            }
+        }
+
+        if !self.stats.operations.is_empty() {
+            writeln!(f, "\n### Operations\n")?;
+            writeln!(f, "| Operation | Uses |")?;
+            writeln!(f, "| --- | ---: |")?;
+            let mut operations: Vec<_> = self.stats.operations.iter().collect();
+            operations.sort_by(|(_, a), (_, b)| b.cmp(a));
+            for (operation, count) in operations {
+                writeln!(f, "| `{}` | {} |", operation, count)?;
+            }
+        }
+
+        if !self.capabilities.is_empty() {
+            let mut operations = BTreeSet::new();
+            let mut operators = BTreeSet::new();
+            let mut events = BTreeSet::new();
+            let mut schema_version = "1.0";
+            for capabilities in self.capabilities.values() {
+                operations.extend(capabilities.test_runner_operations.iter());
+                operators.extend(capabilities.special_operators.iter());
+                events.extend(capabilities.events.iter());
+                if report::older_than(schema_version, &capabilities.schema_version) {
+                    schema_version = &capabilities.schema_version;
+                }
+            }
+            writeln!(f, "\n### Runner capabilities\n")?;
+            writeln!(f, "- Schema version: {}", schema_version)?;
+            write_list(f, "`testRunner` operations", &operations)?;
+            write_list(f, "Special operators", &operators)?;
+            write_list(f, "Events", &events)?;
+        }
+
+        if !self.approximations.is_empty() {
+            writeln!(f, "\n### Dropped or approximated\n")?;
+            for (approximation, count) in self.approximations.iter() {
+                writeln!(f, "- {} ({}×)", approximation, count)?;
+            }
+        }
+
+        if !self.skipped.is_empty() {
+            writeln!(f, "\n### Not converted\n")?;
+            for (file, reason) in self.skipped.iter() {
+                writeln!(f, "- `{}`: {}", file, reason)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a list item of code-formatted names, if there are any.
+fn write_list(f: &mut fmt::Formatter<'_>, label: &str, names: &BTreeSet<&String>) -> fmt::Result {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<_> = names.iter().map(|name| format!("`{}`", name)).collect();
+    writeln!(f, "- {}: {}", label, names.join(", "))
+}
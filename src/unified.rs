@@ -0,0 +1,1215 @@
+//! The unified test format.
+
+use std::collections::{BTreeSet, HashSet};
+
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crud_v2::{self, CmapEvent, CommandEvent, Expectation, OperationResult, SdamEvent, TestData},
+    entities::{self, Entity, EntityRegistry},
+    matchers,
+    normalize,
+    operations::OperationContext,
+    report::{WarningKind, Warnings},
+    unknown_operation_placeholder,
+    ConversionError,
+    ConvertOptions,
+};
+
+pub static SERVER_DESCRIPTION_CHANGED: &str = "serverDescriptionChangedEvent";
+pub static POOL_CLEARED: &str = "poolClearedEvent";
+pub static POOL_READY: &str = "poolReadyEvent";
+pub static CONNECTION_CREATED: &str = "connectionCreatedEvent";
+
+/// The legacy operations the converter knows how to convert. Anything else is passed through
+/// as-is, which may or may not be correct.
+pub(crate) static KNOWN_OPERATIONS: &[&str] = &[
+    // collection, database, and client operations
+    "aggregate",
+    "bulkWrite",
+    "count",
+    "countDocuments",
+    "createCollection",
+    "createIndex",
+    "deleteMany",
+    "deleteOne",
+    "distinct",
+    "dropCollection",
+    "dropIndex",
+    "estimatedDocumentCount",
+    "find",
+    "findOne",
+    "findOneAndDelete",
+    "findOneAndReplace",
+    "findOneAndUpdate",
+    "insertMany",
+    "insertOne",
+    "listCollectionNames",
+    "listCollections",
+    "listDatabaseNames",
+    "listDatabases",
+    "listIndexNames",
+    "listIndexes",
+    "mapReduce",
+    "modifyCollection",
+    "replaceOne",
+    "runAdminCommand",
+    "runCommand",
+    "updateMany",
+    "updateOne",
+    "watch",
+    // GridFS operations
+    "delete",
+    "download",
+    "downloadByName",
+    "upload",
+    // session, cursor, and change stream operations
+    "abortTransaction",
+    "close",
+    "commitTransaction",
+    "endSession",
+    "next",
+    "startTransaction",
+    "withTransaction",
+    // test runner operations
+    "assertCollectionExists",
+    "assertCollectionNotExists",
+    "assertDifferentLsidOnLastTwoCommands",
+    "assertEventCount",
+    "assertIndexExists",
+    "assertIndexNotExists",
+    "assertSameLsidOnLastTwoCommands",
+    "assertSessionDirty",
+    "assertSessionNotDirty",
+    "assertSessionPinned",
+    "assertSessionTransactionState",
+    "assertSessionUnpinned",
+    "configureFailPoint",
+    "recordPrimary",
+    "runOnThread",
+    "startThread",
+    "targetedFailPoint",
+    "wait",
+    "waitForEvent",
+    "waitForPrimaryChange",
+    "waitForThread",
+];
+
+/// The names of the database and collection that the file being converted runs its tests
+/// against.
+#[derive(Debug, Clone, Copy)]
+pub struct Namespace<'a> {
+    pub database_name: &'a str,
+    pub collection_name: &'a str,
+    pub bucket_name: Option<&'a str>,
+}
+
+/// The namespace and options of the database and collection entities an operation runs on,
+/// where they differ from the test's own.
+#[derive(Debug, Default, PartialEq)]
+struct EntityOptions {
+    database_name: Option<String>,
+    collection_name: Option<String>,
+    database_options: Option<Document>,
+    collection_options: Option<Document>,
+}
+
+impl EntityOptions {
+    fn of(op: &crud_v2::Operation, namespace: &Namespace) -> Self {
+        Self {
+            database_name: op
+                .database
+                .clone()
+                .filter(|name| name != namespace.database_name),
+            collection_name: op
+                .collection
+                .clone()
+                .filter(|name| name != namespace.collection_name),
+            database_options: op.database_options.clone(),
+            collection_options: op.collection_options.clone(),
+        }
+    }
+
+    /// Whether the operation needs a database entity of its own.
+    fn has_database(&self) -> bool {
+        self.database_name.is_some() || self.database_options.is_some()
+    }
+}
+
+impl Namespace<'_> {
+    /// A reference to the given database name, using the anchored name when it's the test
+    /// database.
+    pub(crate) fn database_name_ref(&self, name: String) -> String {
+        if name == self.database_name {
+            Entity::DatabaseName.reference()
+        } else {
+            name
+        }
+    }
+
+    /// A reference to the given collection name, using the anchored name when it's the test
+    /// collection.
+    pub(crate) fn collection_name_ref(&self, name: String) -> String {
+        if name == self.collection_name {
+            Entity::CollectionName.reference()
+        } else {
+            name
+        }
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TestFile {
+    pub description: String,
+    pub schema_version: String,
+    pub run_on_requirements: Option<Vec<RunOnRequirements>>,
+    pub create_entities: Option<Vec<Bson>>,
+    pub initial_data: Option<Vec<InitialData>>,
+    pub tests: Vec<Test>,
+}
+
+/// An `initialData` or `outcome` entry. An empty `documents` array is always serialized, since
+/// it still instructs the runner to create the collection.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct InitialData {
+    pub collection_name: String,
+    pub database_name: String,
+    pub create_options: Option<Document>,
+    pub documents: Vec<Document>,
+}
+
+impl InitialData {
+    /// Whether this entry is for the same collection and has the same documents as another,
+    /// whichever of them defines the anchors of the collection's names.
+    pub(crate) fn same_as(&self, other: &InitialData) -> bool {
+        entities::same_value(&self.collection_name, &other.collection_name)
+            && entities::same_value(&self.database_name, &other.database_name)
+            && self.create_options == other.create_options
+            && self.documents == other.documents
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub enum CreateEntity {
+    Client(ClientEntity),
+    Database(DatabaseEntity),
+    Collection(CollectionEntity),
+    Session(SessionEntity),
+    Bucket(BucketEntity),
+    Thread { id: String },
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ClientEntity {
+    pub id: String,
+    pub observe_events: Option<BTreeSet<String>>,
+    pub ignore_command_monitoring_events: Option<Vec<String>>,
+    pub observe_sensitive_commands: Option<bool>,
+    pub uri_options: Option<Document>,
+    pub use_multiple_mongoses: Option<bool>,
+    pub auto_encrypt_opts: Option<Document>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DatabaseEntity {
+    pub id: String,
+    pub client: String,
+    pub database_name: String,
+    pub database_options: Option<Document>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CollectionEntity {
+    pub id: String,
+    pub database: String,
+    pub collection_name: String,
+    pub collection_options: Option<Document>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BucketEntity {
+    pub id: String,
+    pub database: String,
+    pub bucket_options: Option<Document>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SessionEntity {
+    pub id: String,
+    pub client: String,
+    pub session_options: Option<Document>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Test {
+    pub description: String,
+    pub run_on_requirements: Option<Vec<RunOnRequirements>>,
+    pub skip_reason: Option<String>,
+    pub operations: Vec<Operation>,
+    pub expect_events: Option<Vec<ExpectEvents>>,
+    pub outcome: Option<Vec<InitialData>>,
+
+    /// Whether the outcome is the same as the file's initial data, in which case it's written as
+    /// an alias of it.
+    #[serde(skip)]
+    pub outcome_is_initial_data: bool,
+
+    /// The entities this test defines and refers to.
+    #[serde(skip)]
+    pub entities: EntityRegistry,
+}
+
+impl Test {
+    pub(crate) fn from_crud_v2(
+        mut old: crud_v2::Test,
+        namespace: Namespace,
+        options: &ConvertOptions,
+        warnings: &Warnings,
+    ) -> Result<Self, ConversionError> {
+        let start = warnings.len();
+        let mut operations = Vec::new();
+        // change streams tests watch their target before running their operations, and assert
+        // on what the change stream returned after them.
+        let change_stream_result = match old.target {
+            Some(target) => {
+                let mut arguments = doc! {
+                    "pipeline": old.change_stream_pipeline.take().unwrap_or_default(),
+                };
+                arguments.extend(old.change_stream_options.take().unwrap_or_default());
+                old.operations.insert(
+                    0,
+                    crud_v2::Operation {
+                        name: "watch".to_string(),
+                        object: target.object().to_string(),
+                        database: None,
+                        collection: None,
+                        command_name: None,
+                        arguments: Some(arguments),
+                        collection_options: None,
+                        database_options: None,
+                        error: None,
+                        result: None,
+                    },
+                );
+                old.result.take()
+            }
+            None => None,
+        };
+        let run_on_requirements = old.run_on().map(|run_on| {
+            RunOnRequirements::from_crud_v2(run_on, options.normalize_topologies, warnings)
+        });
+        let mut observed_events: BTreeSet<String> =
+            old.observed_events().into_iter().map(String::from).collect();
+        observed_events.extend(options.observe_events.iter().cloned());
+        let observe_sensitive_commands = old.expects_sensitive_commands();
+        let mut session_names = old.session_names();
+        let thread_names = old.thread_names();
+        let context = OperationContext {
+            namespace,
+            threads: &thread_names,
+            operations: &options.operations,
+            command_name: None,
+            strict: options.strict,
+            warnings,
+        };
+        if let Some(fp) = old.fail_point {
+            operations.push(Operation {
+                name: "failPoint".to_string(),
+                object: "testRunner".to_string(),
+                arguments: Some(doc! {
+                    "client": Entity::SetupClient.reference(),
+                    "failPoint": fp,
+                }),
+                save_result_as_entity: None,
+                expect_result: None,
+                expect_error: None,
+            });
+        }
+
+        let ignore_command_monitoring_events = if options.ignore_command_monitoring_events
+            && old.expectations.is_some()
+        {
+            Some(options.ignored_commands.clone())
+        } else {
+            None
+        };
+
+        let mut client_options = old.client_uri.clone();
+        let auto_encrypt_opts = client_options
+            .as_mut()
+            .and_then(|options| normalize::auto_encrypt_opts(options, warnings));
+
+        let expect_events = old.expectations.map(|old_events| {
+            let mut command_events = Vec::new();
+            let mut cmap_events = Vec::new();
+            let mut sdam_events = Vec::new();
+            for event in old_events {
+                match event {
+                    Expectation::Command(event) => {
+                        command_events.push(ExpectEvent::from_command_event(event, namespace))
+                    }
+                    Expectation::Cmap(event) => cmap_events.push(event.into()),
+                    Expectation::Sdam(event) => sdam_events.push(event.into()),
+                }
+            }
+
+            // an empty expectations list still asserts that no commands were observed.
+            let only_commands = cmap_events.is_empty() && sdam_events.is_empty();
+            [
+                ("command", command_events),
+                ("cmap", cmap_events),
+                ("sdam", sdam_events),
+            ]
+            .into_iter()
+            .filter(|(event_type, events)| {
+                !events.is_empty() || (*event_type == "command" && only_commands)
+            })
+            .map(|(event_type, events)| ExpectEvents {
+                client: Entity::Client.reference(),
+                event_type: event_type.to_string(),
+                events,
+            })
+            .collect()
+        });
+
+        // sessions that only the expectations refer to, by asserting on their lsids, still
+        // need to be created.
+        let mut expected = EntityRegistry::default();
+        expected.record(&bson::to_bson(&expect_events)?);
+        session_names.extend(expected.sessions().map(String::from));
+
+        let mut ents = vec![
+            CreateEntity::Client(ClientEntity {
+                id: Entity::Client.definition(),
+                observe_events: (!observed_events.is_empty()).then_some(observed_events),
+                ignore_command_monitoring_events,
+                observe_sensitive_commands: observe_sensitive_commands.then_some(true),
+                uri_options: client_options
+                    .filter(|options| !options.is_empty())
+                    .map(|options| normalize::uri_options(options, warnings)),
+                // tests that use several mongoses need a client of their own, which also keeps
+                // their entities from being hoisted along with other tests'.
+                use_multiple_mongoses: old.use_multiple_mongoses,
+                auto_encrypt_opts,
+            }),
+            CreateEntity::Database(DatabaseEntity {
+                id: Entity::Database.definition(),
+                client: Entity::Client.reference(),
+                database_name: Entity::DatabaseName.reference(),
+                database_options: None,
+            }),
+            CreateEntity::Collection(CollectionEntity {
+                id: Entity::Collection.definition(),
+                database: Entity::Database.reference(),
+                collection_name: Entity::CollectionName.reference(),
+                collection_options: None,
+            }),
+        ];
+
+        // operations that run in another namespace or specify database or collection options
+        // need their own entities, one per distinct combination of namespace and options.
+        let mut entity_options: Vec<EntityOptions> = Vec::new();
+        for old_op in old.operations.iter() {
+            let op_options = EntityOptions::of(old_op, &namespace);
+            if op_options != EntityOptions::default() && !entity_options.contains(&op_options) {
+                entity_options.push(op_options);
+            }
+        }
+        for (i, op_options) in entity_options.iter().enumerate() {
+            let EntityOptions {
+                database_name,
+                collection_name,
+                database_options,
+                collection_options,
+            } = op_options;
+            let mut database_options = database_options.clone();
+            let mut collection_options = collection_options.clone();
+            for options in [&mut database_options, &mut collection_options]
+                .into_iter()
+                .flatten()
+            {
+                normalize::timeout_ms(options);
+            }
+            let database = if op_options.has_database() {
+                ents.push(CreateEntity::Database(DatabaseEntity {
+                    id: Entity::ExtraDatabase(i + 1).definition(),
+                    client: Entity::Client.reference(),
+                    database_name: database_name
+                        .clone()
+                        .unwrap_or_else(|| Entity::DatabaseName.reference()),
+                    database_options,
+                }));
+                Entity::ExtraDatabase(i + 1).reference()
+            } else {
+                Entity::Database.reference()
+            };
+            ents.push(CreateEntity::Collection(CollectionEntity {
+                id: Entity::ExtraCollection(i + 1).definition(),
+                database,
+                collection_name: collection_name
+                    .clone()
+                    .unwrap_or_else(|| Entity::CollectionName.reference()),
+                collection_options,
+            }));
+        }
+        if old.operations.iter().any(|op| op.object == "gridfsbucket") {
+            ents.push(CreateEntity::Bucket(BucketEntity {
+                id: Entity::Bucket.definition(),
+                database: Entity::Database.reference(),
+                bucket_options: namespace
+                    .bucket_name
+                    .map(|bucket_name| doc! { "bucketName": bucket_name }),
+            }));
+        }
+        ents.extend(session_names.iter().map(|name| {
+            CreateEntity::Session(SessionEntity {
+                id: Entity::Session(name.to_string()).definition(),
+                client: Entity::Client.reference(),
+                session_options: old
+                    .session_options
+                    .as_mut()
+                    .and_then(|options| options.remove(name))
+                    .map(|options| normalize::session_options(options, warnings)),
+            })
+        }));
+
+        if !ents.is_empty() {
+            operations.push(Operation {
+                name: "createEntities".to_string(),
+                object: "testRunner".to_string(),
+                arguments: Some(doc! {
+                    "entities": bson::to_bson(&ents)?,
+                }),
+                expect_error: None,
+                expect_result: None,
+                save_result_as_entity: None,
+            });
+        }
+
+        // change streams, and cursors that later operations iterate, need to be saved as
+        // entities so that those operations can refer to them.
+        let iterates: Vec<bool> = old
+            .operations
+            .iter()
+            .map(|op| matches!(op.object.as_str(), "cursor" | "changeStream"))
+            .collect();
+        let mut entities = EntityRegistry::default();
+        let mut iterable = None;
+        let mut target_stream = None;
+
+        // the collections the test's aggregations write to in the test database.
+        let aggregation_outputs: Vec<String> = old
+            .operations
+            .iter()
+            .filter_map(crud_v2::Operation::aggregation_output)
+            .filter(|(database, _)| {
+                database
+                    .as_deref()
+                    .is_none_or(|d| d == namespace.database_name)
+            })
+            .map(|(_, collection)| collection)
+            .collect();
+
+        for (i, old_op) in old.operations.into_iter().enumerate() {
+            let _span = tracing::trace_span!("operation", index = i, name = %old_op.name).entered();
+            let writes_output = old_op.aggregation_output().is_some();
+            let op_options = EntityOptions::of(&old_op, &namespace);
+            let object = old_op.object.clone();
+            let start = warnings.len();
+            let mut op = Operation::from_crud_v2(old_op, &context)
+                .map_err(|e| e.in_operation(old.description.as_str(), i))?;
+            // aggregations that write their output somewhere don't return it, so what they wrote
+            // is asserted by the outcome instead.
+            if writes_output {
+                match op.expect_result.take() {
+                    Some(Bson::Array(result)) if result.is_empty() => {}
+                    Some(_) => warnings.warn(WarningKind::DroppedField {
+                        field: "result".to_string(),
+                        reason: "aggregations that write their output don't return it",
+                    }),
+                    None => {}
+                }
+            }
+            warnings.locate(start, |location| {
+                location.operation.get_or_insert(i);
+            });
+            let mut follow_ups = Vec::new();
+
+            if let Some(i) = entity_options.iter().position(|o| *o == op_options) {
+                match object.as_str() {
+                    "collection" => op.object = Entity::ExtraCollection(i + 1).reference(),
+                    "database" if op_options.has_database() => {
+                        op.object = Entity::ExtraDatabase(i + 1).reference()
+                    }
+                    _ => {}
+                }
+            }
+
+            match (op.name.as_str(), object.as_str()) {
+                ("createChangeStream", _) => {
+                    let stream = entities.allocate(Entity::ChangeStream);
+                    op.save_result_as_entity = Some(stream.definition());
+                    iterable = Some(stream.reference());
+                    if i == 0 && old.target.is_some() {
+                        target_stream = Some(stream.clone());
+                    }
+
+                    // legacy tests list the expected changes as the stream's result, whereas
+                    // the unified format asserts on them by iterating the stream.
+                    if let Some(Bson::Document(mut result)) = op.expect_result.take() {
+                        if let Ok(changes) = result.get_array_mut("success") {
+                            for change in changes.drain(..) {
+                                let change = match change {
+                                    Bson::Document(change) => matchers::change_event(change),
+                                    other => other,
+                                };
+                                follow_ups.push(Operation {
+                                    name: "iterateUntilDocumentOrError".to_string(),
+                                    object: stream.reference(),
+                                    expect_result: Some(change),
+                                    ..Default::default()
+                                });
+                            }
+                        } else {
+                            op.expect_result = Some(result.into());
+                        }
+                    }
+                }
+                ("find", _) if iterates[i + 1..].iter().any(|iterates| *iterates) => {
+                    op.name = "createFindCursor".to_string();
+                    op.expect_result = None;
+                    let cursor = entities.allocate(Entity::Cursor);
+                    op.save_result_as_entity = Some(cursor.definition());
+                    iterable = Some(cursor.reference());
+                }
+                ("aggregate", _) if iterates[i + 1..].iter().any(|iterates| *iterates) => {
+                    // there's no collection operation that saves an aggregation's cursor, so the
+                    // aggregate command is run on the database instead.
+                    let extra = entity_options.iter().position(|o| *o == op_options);
+                    let database = match (object.as_str(), extra) {
+                        ("collection", Some(i)) if op_options.has_database() => {
+                            Entity::ExtraDatabase(i + 1).reference()
+                        }
+                        ("collection", _) => Entity::Database.reference(),
+                        _ => op.object.clone(),
+                    };
+                    let aggregate: Bson = match object.as_str() {
+                        "collection" => op_options
+                            .collection_name
+                            .clone()
+                            .map(|name| namespace.collection_name_ref(name))
+                            .unwrap_or_else(|| Entity::CollectionName.reference())
+                            .into(),
+                        _ => 1.into(),
+                    };
+                    if op_options.collection_options.is_some() {
+                        warnings.warn(WarningKind::DroppedField {
+                            field: "collectionOptions".to_string(),
+                            reason: "the aggregation is run as a database command",
+                        });
+                    }
+                    op.name = "createCommandCursor".to_string();
+                    op.object = database;
+                    op.arguments = Some(aggregate_command(aggregate, op.arguments.take()));
+                    op.expect_result = None;
+                    let cursor = entities.allocate(Entity::Cursor);
+                    op.save_result_as_entity = Some(cursor.definition());
+                    iterable = Some(cursor.reference());
+                }
+                (name, "cursor" | "changeStream") => {
+                    op.object = iterable.clone().ok_or_else(|| {
+                        ConversionError::UnsupportedOperation {
+                            name: op.name.clone(),
+                            reason: format!("there is no preceding {} to iterate", object),
+                        }
+                        .in_operation(old.description.as_str(), i)
+                    })?;
+                    if name == "next" {
+                        op.name = "iterateUntilDocumentOrError".to_string();
+                    }
+                }
+                _ => {}
+            }
+
+            operations.push(op);
+            operations.append(&mut follow_ups);
+        }
+
+        if let (Some(stream), Some(result)) = (target_stream, change_stream_result) {
+            for change in result.success.into_iter().flatten() {
+                operations.push(Operation {
+                    name: "iterateUntilDocumentOrError".to_string(),
+                    object: stream.reference(),
+                    expect_result: Some(matchers::change_event(change)),
+                    ..Default::default()
+                });
+            }
+            if let Some(error) = result.error {
+                let expect_error = ExpectError::from_change_stream_error(error);
+                // without any operations to make changes, the error is from opening the change
+                // stream rather than from iterating it.
+                match operations.last_mut() {
+                    Some(op) if op.save_result_as_entity.as_ref() == Some(&stream.definition()) => {
+                        op.save_result_as_entity = None;
+                        op.expect_error = Some(expect_error);
+                    }
+                    _ => operations.push(Operation {
+                        name: "iterateUntilDocumentOrError".to_string(),
+                        object: stream.reference(),
+                        expect_error: Some(expect_error),
+                        ..Default::default()
+                    }),
+                }
+            }
+        }
+
+        let outcome = old.outcome.map(|old_outcome| {
+            let collection = old_outcome.collection;
+            match collection.data {
+                TestData::Single(documents) => vec![InitialData {
+                    database_name: Entity::DatabaseName.reference(),
+                    // an outcome without a collection name is about the test collection,
+                    // unless the test only writes to another one with an aggregation.
+                    collection_name: match (collection.name, aggregation_outputs.as_slice()) {
+                        (Some(name), _) => namespace.collection_name_ref(name),
+                        (None, [output]) => namespace.collection_name_ref(output.clone()),
+                        (None, _) => Entity::CollectionName.reference(),
+                    },
+                    create_options: None,
+                    documents,
+                }],
+                TestData::Many(collections) => {
+                    let mut collections: Vec<_> = collections.into_iter().collect();
+                    collections.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    collections
+                        .into_iter()
+                        .map(|(name, documents)| InitialData {
+                            database_name: Entity::DatabaseName.reference(),
+                            collection_name: namespace.collection_name_ref(name),
+                            create_options: None,
+                            documents,
+                        })
+                        .collect()
+                }
+            }
+        });
+
+        entities.record(&bson::to_bson(&operations)?);
+        entities.record(&bson::to_bson(&expect_events)?);
+        entities.record(&bson::to_bson(&outcome)?);
+
+        warnings.locate(start, |location| {
+            location.test.get_or_insert_with(|| old.description.clone());
+        });
+
+        Ok(Self {
+            description: old.description,
+            run_on_requirements,
+            skip_reason: old.skip_reason,
+            operations,
+            expect_events,
+            outcome,
+            outcome_is_initial_data: false,
+            entities,
+        })
+    }
+}
+
+impl Test {
+    /// The entities this test creates before doing anything else, if any.
+    fn leading_entities(&self) -> Option<&Bson> {
+        let op = self.operations.first()?;
+        if op.name != "createEntities" {
+            return None;
+        }
+        op.arguments.as_ref()?.get("entities")
+    }
+
+    /// Writes the test's outcome as an alias of the file's initial data, which it's the same as.
+    pub(crate) fn alias_outcome(&mut self) {
+        self.outcome_is_initial_data = true;
+        self.recount_entities();
+    }
+
+    /// Creates the given entities before anything else happens in the test, in its leading
+    /// `createEntities` operation if it has one, or a new one otherwise.
+    pub(crate) fn create_first(&mut self, created: Vec<Bson>) {
+        let leading = self
+            .operations
+            .first_mut()
+            .filter(|op| op.name == "createEntities")
+            .and_then(|op| op.arguments.as_mut())
+            .and_then(|arguments| arguments.get_array_mut("entities").ok());
+        match leading {
+            Some(entities) => entities.extend(created),
+            None => self.operations.insert(
+                0,
+                Operation {
+                    name: "createEntities".to_string(),
+                    object: "testRunner".to_string(),
+                    arguments: Some(doc! { "entities": created }),
+                    ..Default::default()
+                },
+            ),
+        }
+        self.recount_entities();
+    }
+
+    /// Rebuilds the registry of entities this test uses after its operations have changed.
+    fn recount_entities(&mut self) {
+        let mut entities = EntityRegistry::default();
+        for value in [
+            bson::to_bson(&self.operations),
+            bson::to_bson(&self.expect_events),
+            bson::to_bson(&self.outcome.as_ref().filter(|_| !self.outcome_is_initial_data)),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            entities.record(&value);
+        }
+        self.entities = entities;
+    }
+}
+
+/// Removes the entities that every test creates up front from the tests and returns them so
+/// that they can be created at the file level instead. Entities are only hoisted if they're
+/// identical across all of the tests and are created before anything else happens in each
+/// test, since e.g. a client created after a fail point is configured may depend on it.
+///
+/// The hoisted entities define the database and collection name anchors.
+pub(crate) fn hoist_entities(tests: &mut [Test]) -> Option<Vec<Bson>> {
+    let entities = tests.first()?.leading_entities()?.clone();
+    if !tests
+        .iter()
+        .all(|test| test.leading_entities() == Some(&entities))
+    {
+        return None;
+    }
+    let mut entities = match entities {
+        Bson::Array(entities) => entities,
+        _ => return None,
+    };
+
+    for test in tests.iter_mut() {
+        test.operations.remove(0);
+        test.recount_entities();
+    }
+
+    for entity in entities.iter_mut() {
+        define_first(
+            entity,
+            &Entity::DatabaseName.reference(),
+            &Entity::DatabaseName.definition(),
+        );
+        define_first(
+            entity,
+            &Entity::CollectionName.reference(),
+            &Entity::CollectionName.definition(),
+        );
+    }
+    Some(entities)
+}
+
+/// Replaces the first occurrence of the given deref placeholder with its definition
+/// placeholder, returning whether it was found.
+fn define_first(value: &mut Bson, deref: &str, definition: &str) -> bool {
+    match value {
+        Bson::String(s) if s == deref => {
+            *s = definition.to_string();
+            true
+        }
+        Bson::Document(doc) => doc
+            .iter_mut()
+            .any(|(_, value)| define_first(value, deref, definition)),
+        Bson::Array(values) => values
+            .iter_mut()
+            .any(|value| define_first(value, deref, definition)),
+        _ => false,
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RunOnRequirements {
+    pub(crate) min_server_version: Option<String>,
+    pub(crate) max_server_version: Option<String>,
+    pub(crate) topologies: Option<Vec<String>>,
+    pub(crate) serverless: Option<crud_v2::Serverless>,
+    pub(crate) server_parameters: Option<Document>,
+    pub(crate) auth: Option<bool>,
+    pub(crate) csfle: Option<bool>,
+}
+
+impl RunOnRequirements {
+    /// Converts legacy `runOn` entries, rewriting topology names to the modern vocabulary when
+    /// `normalize` is set.
+    pub(crate) fn from_crud_v2(
+        run_on: Vec<crud_v2::RunOn>,
+        normalize: bool,
+        warnings: &Warnings,
+    ) -> Vec<Self> {
+        run_on
+            .into_iter()
+            .map(|run_on| {
+                let mut requirements = Self::from(run_on);
+                if let Some(ref mut topologies) = requirements.topologies {
+                    if normalize {
+                        normalize_topologies(topologies);
+                    } else if topologies.iter().any(|t| t == "sharded-replicaset") {
+                        warnings.warn(WarningKind::DeprecatedTopology(
+                            "sharded-replicaset".to_string(),
+                        ));
+                    }
+                }
+                requirements
+            })
+            .collect()
+    }
+}
+
+fn normalize_topologies(topologies: &mut Vec<String>) {
+    for topology in topologies.iter_mut() {
+        if topology == "sharded-replicaset" {
+            *topology = "sharded".to_string();
+        }
+    }
+    let mut seen = HashSet::new();
+    topologies.retain(|topology| seen.insert(topology.clone()));
+}
+
+/// The `createCommandCursor` arguments that run the aggregate command equivalent to an
+/// `aggregate` operation with the given arguments, aggregating `aggregate`: a collection name,
+/// or 1 for a database aggregation.
+fn aggregate_command(aggregate: Bson, arguments: Option<Document>) -> Document {
+    let mut command = doc! { "aggregate": aggregate };
+    let mut cursor = Document::new();
+    let mut cursor_arguments = Document::new();
+    for (key, value) in arguments.into_iter().flatten() {
+        match key.as_str() {
+            "session" => {
+                cursor_arguments.insert(key, value);
+            }
+            "batchSize" => {
+                cursor.insert("batchSize", value.clone());
+                cursor_arguments.insert(key, value);
+            }
+            _ => {
+                command.insert(key, value);
+            }
+        }
+    }
+    command.entry("pipeline".to_string()).or_insert_with(|| Bson::Array(Vec::new()));
+    command.insert("cursor", cursor);
+    let mut arguments = doc! { "commandName": "aggregate", "command": command };
+    arguments.extend(cursor_arguments);
+    arguments
+}
+
+impl From<crud_v2::RunOn> for RunOnRequirements {
+    fn from(old: crud_v2::RunOn) -> Self {
+        Self {
+            min_server_version: old.min_server_version,
+            max_server_version: old.max_server_version,
+            topologies: old.topology,
+            serverless: old.serverless,
+            server_parameters: None,
+            auth: old.auth_enabled,
+            csfle: None,
+        }
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Operation {
+    pub name: String,
+    pub object: String,
+    pub arguments: Option<Document>,
+    pub save_result_as_entity: Option<String>,
+    pub expect_result: Option<Bson>,
+    pub expect_error: Option<ExpectError>,
+}
+
+impl Operation {
+    pub(crate) fn from_crud_v2(
+        old_op: crud_v2::Operation,
+        context: &OperationContext<'_>,
+    ) -> Result<Self, ConversionError> {
+        let mut name = old_op.name;
+        let mut arguments = old_op.arguments;
+        if !KNOWN_OPERATIONS.contains(&name.as_str()) && !context.operations.contains(&name) {
+            if context.strict {
+                return Err(ConversionError::UnsupportedOperation {
+                    name,
+                    reason: "the operation is not recognized".to_string(),
+                });
+            }
+            context
+                .warnings
+                .warn(WarningKind::UnknownOperation(name.clone()));
+            name = unknown_operation_placeholder(&name);
+        }
+        if let Some(ref mut arguments) = arguments {
+            normalize::read_preference(arguments);
+            normalize::timeout_ms(arguments);
+            // e.g. CRUD operations in transactions, targetedFailPoint, assertSessionPinned
+            Operation::convert_session_argument(arguments);
+        }
+        let object = match old_op.object.as_str() {
+            "collection" => Entity::Collection.reference(),
+            "database" => Entity::Database.reference(),
+            "gridfsbucket" => Entity::Bucket.reference(),
+            "client" => Entity::Client.reference(),
+            session if session.starts_with("session") => {
+                Entity::Session(session.to_string()).reference()
+            }
+            _ => old_op.object,
+        };
+
+        let command_name = old_op.command_name;
+        let context = OperationContext {
+            command_name: command_name.as_deref(),
+            ..*context
+        };
+        let mut op = Operation {
+            name,
+            object,
+            arguments,
+            ..Default::default()
+        };
+        if let Some(converter) = context.operations.get(&op.name) {
+            converter.convert(&mut op, &context)?;
+        }
+        let name = op.name.as_str();
+
+        let (expect_result, expect_error) = match (old_op.result, old_op.error) {
+            (Some(OperationResult::Success(Bson::Document(response))), Some(true))
+                if matchers::is_error_response(&response) =>
+            {
+                (
+                    None,
+                    Some(ExpectError {
+                        is_error: Some(true),
+                        error_response: Some(response),
+                        ..Default::default()
+                    }),
+                )
+            }
+            (Some(OperationResult::Success(b)), Some(true)) => {
+                // A partially failed bulk write asserts both the error and the writes that
+                // made it through.
+                (
+                    None,
+                    Some(ExpectError {
+                        is_error: Some(true),
+                        expect_result: Some(matchers::convert_result(name, b)),
+                        ..Default::default()
+                    }),
+                )
+            }
+            (Some(OperationResult::Success(b)), _) => {
+                (Some(matchers::convert_result(name, b)), None)
+            }
+            (Some(OperationResult::Error(e)), error) => {
+                if error == Some(false) {
+                    context
+                        .warnings
+                        .warn(WarningKind::ContradictoryError(name.to_string()));
+                }
+                let mut expect_error = ExpectError {
+                    is_error: error.filter(|error| *error),
+                    error_contains: e.error_contains,
+                    error_code: e.error_code,
+                    error_code_name: e.error_code_name,
+                    error_labels_contain: e.error_labels_contain,
+                    error_labels_omit: e.error_labels_omit,
+                    error_response: e.error_response,
+                    expect_result: None,
+                };
+                // expectError can't be empty, so if none of the legacy assertions carried
+                // over, just assert that there was an error.
+                if expect_error == ExpectError::default() {
+                    expect_error.is_error = Some(true);
+                }
+                (None, Some(expect_error))
+            }
+            (None, Some(true)) => (
+                None,
+                Some(ExpectError {
+                    is_error: Some(true),
+                    ..Default::default()
+                }),
+            ),
+            (None, _) => (None, None),
+        };
+
+        op.expect_result = expect_result;
+        op.expect_error = expect_error;
+        Ok(op)
+    }
+
+    /// Rewrites a `session` argument from the legacy session name into a reference to the
+    /// corresponding session entity.
+    fn convert_session_argument(arguments: &mut Document) {
+        if let Ok(name) = arguments.get_str("session") {
+            let session = Entity::Session(name.to_string()).reference();
+            arguments.insert("session", session);
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ExpectEvents {
+    pub(crate) client: String,
+    #[serde(default = "default_event_type")]
+    pub(crate) event_type: String,
+    pub(crate) events: Vec<ExpectEvent>,
+}
+
+fn default_event_type() -> String {
+    "command".to_string()
+}
+
+#[allow(clippy::enum_variant_names)]
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub enum ExpectEvent {
+    #[serde(rename_all = "camelCase")]
+    CommandStartedEvent {
+        command: Document,
+        command_name: Option<String>,
+        database_name: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    CommandSucceededEvent {
+        reply: Option<Document>,
+        command_name: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    CommandFailedEvent { command_name: Option<String> },
+    PoolClearedEvent(Document),
+    PoolReadyEvent(Document),
+    ConnectionCreatedEvent(Document),
+    ServerDescriptionChangedEvent(Document),
+    TopologyDescriptionChangedEvent(Document),
+}
+
+impl ExpectEvent {
+    fn from_command_event(event: CommandEvent, namespace: Namespace) -> Self {
+        match event {
+            CommandEvent::Started(mut event) => {
+                matchers::rewrite_session_lsid(&mut event.command);
+                matchers::rewrite_document(&mut event.command);
+                matchers::rewrite_absent_fields(&mut event.command);
+                ExpectEvent::CommandStartedEvent {
+                    command: event.command,
+                    command_name: event.command_name,
+                    // only refer to the anchor when the expectation is actually about the
+                    // test database (e.g. not admin commands).
+                    database_name: event
+                        .database_name
+                        .map(|name| namespace.database_name_ref(name)),
+                }
+            }
+            CommandEvent::Succeeded(mut event) => {
+                if let Some(ref mut reply) = event.reply {
+                    matchers::rewrite_document(reply);
+                }
+                ExpectEvent::CommandSucceededEvent {
+                    reply: event.reply,
+                    command_name: event.command_name,
+                }
+            }
+            CommandEvent::Failed(event) => ExpectEvent::CommandFailedEvent {
+                command_name: event.command_name,
+            },
+        }
+    }
+}
+
+impl From<SdamEvent> for ExpectEvent {
+    fn from(event: SdamEvent) -> Self {
+        match event {
+            SdamEvent::ServerDescriptionChanged(event) => {
+                Self::ServerDescriptionChangedEvent(event)
+            }
+            SdamEvent::TopologyDescriptionChanged(event) => {
+                Self::TopologyDescriptionChangedEvent(event)
+            }
+        }
+    }
+}
+
+impl From<CmapEvent> for ExpectEvent {
+    fn from(event: CmapEvent) -> Self {
+        match event {
+            CmapEvent::PoolCleared(event) => Self::PoolClearedEvent(event),
+            CmapEvent::PoolReady(event) => Self::PoolReadyEvent(event),
+            CmapEvent::ConnectionCreated(event) => Self::ConnectionCreatedEvent(event),
+        }
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ExpectError {
+    pub(crate) is_error: Option<bool>,
+    pub(crate) error_contains: Option<String>,
+    pub(crate) error_code: Option<i32>,
+    pub(crate) error_code_name: Option<String>,
+    pub(crate) error_labels_contain: Option<Vec<String>>,
+    pub(crate) error_labels_omit: Option<Vec<String>>,
+    pub(crate) error_response: Option<Document>,
+    pub(crate) expect_result: Option<Bson>,
+}
+
+impl ExpectError {
+    /// Converts the error a legacy change streams test expects, which gives the server's error
+    /// `code`, `codeName`, and `errorLabels`.
+    fn from_change_stream_error(error: Document) -> Self {
+        let error_labels_contain = error.get_array("errorLabels").ok().map(|labels| {
+            labels
+                .iter()
+                .filter_map(Bson::as_str)
+                .map(String::from)
+                .collect()
+        });
+        let expect_error = Self {
+            error_code: error.get("code").and_then(Bson::as_i32),
+            error_code_name: error.get_str("codeName").ok().map(String::from),
+            error_labels_contain,
+            ..Default::default()
+        };
+        if expect_error == Self::default() {
+            return Self {
+                is_error: Some(true),
+                ..Default::default()
+            };
+        }
+        expect_error
+    }
+}
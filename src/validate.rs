@@ -0,0 +1,230 @@
+//! Validation of converted files against the structure a driver's unified test runner accepts.
+//!
+//! These types are a trimmed copy of the ones the Rust driver's unified runner deserializes
+//! test files into, keeping its `deny_unknown_fields` strictness but leaving operation
+//! arguments and expected results as untyped documents. Their fields are only deserialized, for
+//! the checks that come with deserializing them, so they're never read.
+
+use bson::{Bson, Document};
+use serde::Deserialize;
+
+use crate::ConversionError;
+
+/// Checks that a converted file would be accepted by the driver's unified test runner.
+pub(crate) fn validate(yaml: &str) -> Result<(), ConversionError> {
+    serde_path_to_error::deserialize::<_, TestFile>(serde_yaml::Deserializer::from_str(yaml))
+        .map(|_| ())
+        .map_err(|e| ConversionError::Invalid {
+            path: e.path().to_string(),
+            source: e.into_inner(),
+        })
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TestFile {
+    description: String,
+    schema_version: String,
+    run_on_requirements: Option<Vec<RunOnRequirement>>,
+    create_entities: Option<Vec<TestFileEntity>>,
+    initial_data: Option<Vec<CollectionData>>,
+    tests: Vec<TestCase>,
+    #[serde(rename = "_yamlAnchors")]
+    yaml_anchors: Option<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RunOnRequirement {
+    min_server_version: Option<String>,
+    max_server_version: Option<String>,
+    topologies: Option<Vec<Topology>>,
+    server_parameters: Option<Document>,
+    serverless: Option<String>,
+    auth: Option<bool>,
+    csfle: Option<bool>,
+}
+
+#[derive(Deserialize)]
+enum Topology {
+    #[serde(rename = "single")]
+    Single,
+    #[serde(rename = "replicaset")]
+    ReplicaSet,
+    #[serde(rename = "sharded")]
+    Sharded,
+    #[serde(rename = "sharded-replicaset")]
+    ShardedReplicaSet,
+    #[serde(rename = "load-balanced")]
+    LoadBalanced,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+enum TestFileEntity {
+    Client(Box<Client>),
+    Database(Database),
+    Collection(Collection),
+    Bucket(Bucket),
+    Session(Session),
+    Thread(Thread),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Client {
+    id: String,
+    uri_options: Option<Document>,
+    use_multiple_mongoses: Option<bool>,
+    observe_events: Option<Vec<String>>,
+    ignore_command_monitoring_events: Option<Vec<String>>,
+    observe_sensitive_commands: Option<bool>,
+    server_api: Option<Document>,
+    store_events_as_entities: Option<Vec<Document>>,
+    auto_encrypt_opts: Option<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Database {
+    id: String,
+    client: String,
+    database_name: String,
+    database_options: Option<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Collection {
+    id: String,
+    database: String,
+    collection_name: String,
+    collection_options: Option<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Bucket {
+    id: String,
+    database: String,
+    bucket_options: Option<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Session {
+    id: String,
+    client: String,
+    session_options: Option<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Thread {
+    id: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CollectionData {
+    collection_name: String,
+    database_name: String,
+    create_options: Option<Document>,
+    documents: Vec<Document>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TestCase {
+    description: String,
+    run_on_requirements: Option<Vec<RunOnRequirement>>,
+    skip_reason: Option<String>,
+    operations: Vec<Operation>,
+    expect_events: Option<Vec<ExpectedEvents>>,
+    outcome: Option<Vec<CollectionData>>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Operation {
+    name: String,
+    object: String,
+    arguments: Option<Document>,
+    expect_error: Option<ExpectError>,
+    expect_result: Option<Bson>,
+    save_result_as_entity: Option<String>,
+    ignore_result_and_error: Option<bool>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ExpectError {
+    is_error: Option<bool>,
+    is_client_error: Option<bool>,
+    is_timeout_error: Option<bool>,
+    error_contains: Option<String>,
+    error_code: Option<i32>,
+    error_code_name: Option<String>,
+    error_labels_contain: Option<Vec<String>>,
+    error_labels_omit: Option<Vec<String>>,
+    error_response: Option<Document>,
+    expect_result: Option<Bson>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ExpectedEvents {
+    client: String,
+    event_type: Option<EventType>,
+    events: Vec<ExpectedEvent>,
+    ignore_extra_events: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum EventType {
+    Command,
+    Cmap,
+    Sdam,
+}
+
+// named after the events, which all end in "Event".
+#[allow(clippy::enum_variant_names)]
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+enum ExpectedEvent {
+    CommandStartedEvent(Document),
+    CommandSucceededEvent(Document),
+    CommandFailedEvent(Document),
+    PoolCreatedEvent(Document),
+    PoolReadyEvent(Document),
+    PoolClearedEvent(Document),
+    PoolClosedEvent(Document),
+    ConnectionCreatedEvent(Document),
+    ConnectionReadyEvent(Document),
+    ConnectionClosedEvent(Document),
+    ConnectionCheckOutStartedEvent(Document),
+    ConnectionCheckOutFailedEvent(Document),
+    ConnectionCheckedOutEvent(Document),
+    ConnectionCheckedInEvent(Document),
+    ServerDescriptionChangedEvent(Document),
+    TopologyDescriptionChangedEvent(Document),
+    ServerHeartbeatStartedEvent(Document),
+    ServerHeartbeatSucceededEvent(Document),
+    ServerHeartbeatFailedEvent(Document),
+}
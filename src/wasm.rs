@@ -0,0 +1,23 @@
+//! JavaScript bindings, so that legacy tests can be converted in the browser.
+//!
+//! The crate is only an rlib by default, so build the cdylib with
+//! `cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! and generate the bindings with `wasm-bindgen --target web`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::ConvertOptions;
+
+/// Converts the YAML contents of a legacy test file into a unified test file. `options` is an
+/// optional object of camelCase option names, e.g. `{ hoistEntities: true }`.
+#[wasm_bindgen]
+pub fn convert(yaml: &str, options: JsValue) -> Result<String, JsValue> {
+    let options: ConvertOptions = if options.is_undefined() || options.is_null() {
+        ConvertOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options)?
+    };
+    crate::convert_str(yaml, &options)
+        .map(|(output, _)| output)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -0,0 +1,53 @@
+//! Tests of the normalization of CRUD operations' arguments.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, ConversionReport, ConvertOptions, WarningKind};
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+/// Converts a findOneAndUpdate with the given `returnDocument`, returning the converted value
+/// along with the report.
+fn return_document(value: &str) -> (Yaml, ConversionReport) {
+    let legacy = format!(
+        r#"
+data: []
+collection_name: coll
+database_name: db
+tests:
+  - description: findOneAndUpdate
+    operations:
+      - name: findOneAndUpdate
+        object: collection
+        arguments:
+          filter: {{}}
+          update: {{ $inc: {{ x: 1 }} }}
+          returnDocument: {}
+"#,
+        value
+    );
+    let (output, report) = convert_str(&legacy, &ConvertOptions::builder().build()).unwrap();
+    let file = yaml(&output);
+    let op = &file["tests"][0]["operations"][1];
+    (op["arguments"]["returnDocument"].clone(), report)
+}
+
+#[test]
+fn return_document_case_is_normalized() {
+    for (value, normalized) in [("after", "After"), ("BEFORE", "Before"), ("After", "After")] {
+        let (converted, report) = return_document(value);
+        assert_eq!(converted, yaml(normalized));
+        assert!(report.warnings.is_empty());
+    }
+}
+
+#[test]
+fn unexpected_return_document_is_unchanged() {
+    let (converted, report) = return_document("Aftr");
+    assert_eq!(converted, yaml("Aftr"));
+    assert!(matches!(
+        report.warnings.as_slice(),
+        [warning] if matches!(warning.kind, WarningKind::UnexpectedValue { .. })
+    ));
+}
@@ -0,0 +1,98 @@
+//! Tests that the cursors later operations iterate are saved as entities for them to refer to.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, ConvertOptions};
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+/// The operations of the only test in the file converted from a legacy test with the given
+/// operations, after the one creating its entities.
+fn converted_operations(operations: &str) -> Vec<Yaml> {
+    let legacy = format!(
+        r#"
+data: [{{ _id: 1 }}, {{ _id: 2 }}]
+collection_name: coll
+database_name: db
+tests:
+  - description: cursor
+    operations: {}
+"#,
+        operations
+    );
+    let (output, _) = convert_str(&legacy, &ConvertOptions::builder().build()).unwrap();
+    let file = yaml(&output);
+    file["tests"][0]["operations"].as_sequence().unwrap()[1..].to_vec()
+}
+
+#[test]
+fn find_cursor_is_saved() {
+    let operations = converted_operations(
+        r#"
+      - name: find
+        object: collection
+        arguments: { filter: {}, batchSize: 1 }
+      - name: iterateOnce
+        object: cursor
+"#,
+    );
+    assert_eq!(operations[0]["name"], yaml("createFindCursor"));
+    assert_eq!(operations[0]["object"], yaml("collection"));
+    assert_eq!(
+        operations[0]["arguments"],
+        yaml("{ filter: {}, batchSize: 1 }")
+    );
+    assert_eq!(operations[0]["saveResultAsEntity"], yaml("cursor0"));
+    assert_eq!(operations[1]["name"], yaml("iterateOnce"));
+    assert_eq!(operations[1]["object"], yaml("cursor0"));
+}
+
+#[test]
+fn aggregate_cursor_is_saved() {
+    let operations = converted_operations(
+        r#"
+      - name: aggregate
+        object: collection
+        arguments: { pipeline: [{ $match: {} }], batchSize: 1 }
+        result: [{ _id: 1 }, { _id: 2 }]
+      - name: next
+        object: cursor
+"#,
+    );
+    assert_eq!(operations[0]["name"], yaml("createCommandCursor"));
+    assert_eq!(operations[0]["object"], yaml("database"));
+    assert_eq!(
+        operations[0]["arguments"],
+        yaml(
+            r#"
+commandName: aggregate
+command:
+  aggregate: coll
+  pipeline: [{ $match: {} }]
+  cursor: { batchSize: 1 }
+batchSize: 1
+"#
+        )
+    );
+    assert_eq!(operations[0]["saveResultAsEntity"], yaml("cursor0"));
+    assert_eq!(operations[1]["name"], yaml("iterateUntilDocumentOrError"));
+    assert_eq!(operations[1]["object"], yaml("cursor0"));
+}
+
+#[test]
+fn aggregate_without_iteration_is_unchanged() {
+    let operations = converted_operations(
+        r#"
+      - name: aggregate
+        object: collection
+        arguments: { pipeline: [] }
+        result: [{ _id: 1 }, { _id: 2 }]
+"#,
+    );
+    assert_eq!(operations[0]["name"], yaml("aggregate"));
+    assert_eq!(
+        operations[0]["expectResult"],
+        yaml("[{ _id: 1 }, { _id: 2 }]")
+    );
+}
@@ -0,0 +1,89 @@
+//! Tests of downgrading unified files written by hand, like the ones in the specifications
+//! repository, rather than by the converter.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{downgrade::downgrade_str, ConversionError};
+
+/// A unified file in the style of the specifications repository's CRUD tests, using fields the
+/// converter doesn't emit.
+static UNIFIED_FILE: &str = r#"
+description: "countDocuments-comment"
+
+schemaVersion: "1.4"
+
+runOnRequirements:
+  - minServerVersion: "4.4"
+    serverless: forbid
+
+createEntities:
+  - client:
+      id: &client0 client0
+      observeEvents: [ commandStartedEvent ]
+  - database:
+      id: &database0 database0
+      client: *client0
+      databaseName: &database0Name crud-tests
+  - collection:
+      id: &collection0 collection0
+      database: *database0
+      collectionName: &collection0Name coll0
+
+initialData:
+  - collectionName: *collection0Name
+    databaseName: *database0Name
+    documents:
+      - { _id: 1, x: 11 }
+      - { _id: 2, x: 22 }
+
+tests:
+  - description: "countDocuments with document comment"
+    runOnRequirements:
+      - minServerVersion: "4.4"
+        topologies: [ single, replicaset ]
+        serverless: allow
+    skipReason: "waiting on a server fix"
+    operations:
+      - name: countDocuments
+        object: *collection0
+        arguments:
+          filter: {}
+          comment: { key: "value" }
+        expectResult: 2
+    expectEvents:
+      - client: *client0
+        events:
+          - commandStartedEvent:
+              command:
+                aggregate: *collection0Name
+                pipeline:
+                  - $match: {}
+                  - $group: { _id: 1, n: { $sum: 1 } }
+                comment: { key: "value" }
+              commandName: aggregate
+              databaseName: *database0Name
+"#;
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+#[test]
+fn serverless_and_skip_reason_survive_downgrade() {
+    let legacy = yaml(&downgrade_str(UNIFIED_FILE).unwrap());
+    assert_eq!(legacy["runOn"][0]["serverless"], yaml("forbid"));
+    let test = &legacy["tests"][0];
+    assert_eq!(test["runOn"][0]["serverless"], yaml("allow"));
+    assert_eq!(test["skipReason"], yaml("waiting on a server fix"));
+}
+
+#[test]
+fn server_parameters_are_inexpressible() {
+    let unified = UNIFIED_FILE.replace(
+        "    serverless: forbid\n",
+        "    serverParameters: { enableTestCommands: true }\n",
+    );
+    assert!(matches!(
+        downgrade_str(&unified),
+        Err(ConversionError::Inexpressible(_))
+    ));
+}
@@ -0,0 +1,58 @@
+//! Tests of the conversion of the legacy test runner's event assertions.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, downgrade::downgrade_str, ConvertOptions};
+
+static LEGACY_FILE: &str = r#"
+data: []
+collection_name: coll
+database_name: db
+tests:
+  - description: wait for a connection
+    operations:
+      - name: find
+        object: collection
+        arguments: { filter: {} }
+      - name: waitForEvent
+        object: testRunner
+        arguments: { event: ConnectionCreatedEvent, count: 1 }
+      - name: assertEventCount
+        object: testRunner
+        arguments: { event: ConnectionCreatedEvent, count: 1 }
+"#;
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+#[test]
+fn connection_created_event_count() {
+    let (output, _) = convert_str(LEGACY_FILE, &ConvertOptions::builder().build()).unwrap();
+    let file = yaml(&output);
+    let operations = file["tests"][0]["operations"].as_sequence().unwrap();
+    for op in &operations[2..] {
+        assert_eq!(
+            op["arguments"]["event"],
+            yaml("{ connectionCreatedEvent: {} }")
+        );
+        assert_eq!(op["arguments"]["count"], yaml("1"));
+    }
+    let client = &operations[0]["arguments"]["entities"][0]["client"];
+    assert!(client["observeEvents"]
+        .as_sequence()
+        .unwrap()
+        .contains(&yaml("connectionCreatedEvent")));
+}
+
+#[test]
+fn connection_created_event_count_survives_downgrade() {
+    let (output, _) = convert_str(LEGACY_FILE, &ConvertOptions::builder().build()).unwrap();
+    let legacy = yaml(&downgrade_str(&output).unwrap());
+    let operations = &legacy["tests"][0]["operations"];
+    for i in [1, 2] {
+        assert_eq!(
+            operations[i]["arguments"],
+            yaml("{ event: ConnectionCreatedEvent, count: 1 }")
+        );
+    }
+}
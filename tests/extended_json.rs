@@ -0,0 +1,88 @@
+//! Tests that values YAML can only express as extended JSON keep their types through conversion.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, downgrade::downgrade_str, ConvertOptions};
+
+static LEGACY_FILE: &str = r#"
+data:
+  - _id: { $oid: "000000000000000000000001" }
+    int: 1
+    negative: -1
+    long: { $numberLong: "7" }
+    negativeLong: { $numberLong: "-7" }
+    bigInt: 5000000000
+    binary: { $binary: { base64: "AAEC", subType: "00" } }
+    uuid: { $binary: { base64: "AAECAwQFBgcICQoLDA0ODw==", subType: "04" } }
+    date: { $date: { $numberLong: "1577836800000" } }
+collection_name: coll
+database_name: db
+tests:
+  - description: find
+    operations:
+      - name: find
+        object: collection
+        arguments: { filter: { long: { $numberLong: "7" } } }
+        result:
+          - _id: { $oid: "000000000000000000000001" }
+            long: { $numberLong: "7" }
+"#;
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+fn converted() -> Yaml {
+    let (output, _) = convert_str(LEGACY_FILE, &ConvertOptions::builder().build()).unwrap();
+    yaml(&output)
+}
+
+#[test]
+fn extended_json_survives_conversion() {
+    let file = converted();
+    let document = &file["initialData"][0]["documents"][0];
+    assert_eq!(document["_id"], yaml(r#"$oid: "000000000000000000000001""#));
+    assert_eq!(document["long"], yaml(r#"$numberLong: "7""#));
+    assert_eq!(document["negativeLong"], yaml(r#"$numberLong: "-7""#));
+    assert_eq!(
+        document["binary"],
+        yaml(r#"$binary: { base64: "AAEC", subType: "00" }"#)
+    );
+    assert_eq!(
+        document["uuid"],
+        yaml(r#"$binary: { base64: "AAECAwQFBgcICQoLDA0ODw==", subType: "04" }"#)
+    );
+    assert_eq!(
+        document["date"],
+        yaml(r#"$date: { $numberLong: "1577836800000" }"#)
+    );
+
+    let find = &file["tests"][0]["operations"][1];
+    assert_eq!(
+        find["arguments"]["filter"]["long"],
+        yaml(r#"$numberLong: "7""#)
+    );
+    assert_eq!(find["expectResult"][0]["long"], yaml(r#"$numberLong: "7""#));
+}
+
+#[test]
+fn plain_integers_stay_plain() {
+    let file = converted();
+    let document = &file["initialData"][0]["documents"][0];
+    assert_eq!(document["int"], yaml("1"));
+    assert_eq!(document["negative"], yaml("-1"));
+    // integers that don't fit in 32 bits are 64-bit whichever way they're written.
+    assert_eq!(document["bigInt"], yaml(r#"$numberLong: "5000000000""#));
+}
+
+#[test]
+fn extended_json_survives_downgrade() {
+    let (output, _) = convert_str(LEGACY_FILE, &ConvertOptions::builder().build()).unwrap();
+    let legacy = yaml(&downgrade_str(&output).unwrap());
+    let document = &legacy["data"][0];
+    assert_eq!(document["negative"], yaml("-1"));
+    assert_eq!(document["long"], yaml(r#"$numberLong: "7""#));
+    assert_eq!(
+        document["binary"],
+        yaml(r#"$binary: { base64: "AAEC", subType: "00" }"#)
+    );
+}
@@ -0,0 +1,236 @@
+//! Property tests of invariants that every converted file should satisfy, whatever legacy test
+//! it was converted from.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use proptest::prelude::*;
+use regex::Regex;
+use serde_json::{json, Value};
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, ConvertOptions};
+
+fn session_name() -> impl Strategy<Value = String> {
+    prop_oneof![Just("session0".to_string()), Just("session1".to_string())]
+}
+
+fn operation() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        (0..10i32, any::<bool>()).prop_map(|(id, check_result)| {
+            let mut op = json!({
+                "name": "insertOne",
+                "object": "collection",
+                "arguments": { "document": { "_id": id } },
+            });
+            if check_result {
+                op["result"] = json!({ "insertedId": id });
+            }
+            op
+        }),
+        (0..10i32).prop_map(|id| json!({
+            "name": "find",
+            "object": "collection",
+            "arguments": { "filter": { "_id": id } },
+            "result": [{ "_id": id }],
+        })),
+        (0..10i32, session_name()).prop_map(|(id, session)| json!({
+            "name": "deleteOne",
+            "object": "collection",
+            "arguments": { "filter": { "_id": id }, "session": session },
+            "result": { "deletedCount": 1 },
+        })),
+        (session_name(), prop_oneof![Just("startTransaction"), Just("commitTransaction")])
+            .prop_map(|(session, name)| json!({ "name": name, "object": session })),
+        Just(json!({
+            "name": "runCommand",
+            "object": "database",
+            "command_name": "ping",
+            "arguments": { "command": { "ping": 1 } },
+        })),
+        Just(json!({
+            "name": "find",
+            "object": "collection",
+            "error": true,
+            "result": { "errorContains": "boom" },
+        })),
+        Just(json!({
+            "name": "configureFailPoint",
+            "object": "testRunner",
+            "arguments": {
+                "failPoint": {
+                    "configureFailPoint": "failCommand",
+                    "mode": { "times": 1 },
+                    "data": { "failCommands": ["insert"], "closeConnection": true },
+                },
+            },
+        })),
+        "[a-z]{3,8}Widget".prop_map(|name| json!({ "name": name, "object": "collection" })),
+    ]
+}
+
+fn expectation() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(json!({
+            "command_started_event": { "command": { "insert": "coll" }, "command_name": "insert" },
+        })),
+        session_name().prop_map(|session| json!({
+            "command_started_event": {
+                "command": { "delete": "coll", "lsid": session },
+                "command_name": "delete",
+            },
+        })),
+        Just(json!({ "pool_cleared_event": {} })),
+        Just(json!({ "connection_created_event": {} })),
+        Just(json!({ "server_description_changed_event": {} })),
+    ]
+}
+
+fn test() -> impl Strategy<Value = Value> {
+    (
+        "[a-z ]{1,20}",
+        prop::collection::vec(operation(), 0..8),
+        prop::option::of(prop::collection::vec(expectation(), 0..5)),
+        prop::option::of(any::<bool>()),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(description, operations, expectations, retry_writes, fail_point)| {
+                let mut test = json!({ "description": description, "operations": operations });
+                if let Some(expectations) = expectations {
+                    test["expectations"] = expectations.into();
+                }
+                if let Some(retry_writes) = retry_writes {
+                    test["clientOptions"] = json!({ "retryWrites": retry_writes });
+                }
+                if fail_point {
+                    test["failPoint"] = json!({
+                        "configureFailPoint": "failCommand",
+                        "mode": "alwaysOn",
+                        "data": { "failCommands": ["find"], "errorCode": 91 },
+                    });
+                }
+                test
+            },
+        )
+}
+
+/// A legacy test file, as YAML.
+fn test_file() -> impl Strategy<Value = String> {
+    (
+        prop::collection::vec(test(), 1..4),
+        prop::collection::vec(0..10i32, 0..3),
+    )
+        .prop_map(|(tests, ids)| {
+            let file = json!({
+                "database_name": "db",
+                "collection_name": "coll",
+                "data": ids.into_iter().map(|id| json!({ "_id": id })).collect::<Vec<_>>(),
+                "tests": tests,
+            });
+            serde_yaml::to_string(&file).unwrap()
+        })
+}
+
+fn options() -> impl Strategy<Value = ConvertOptions> {
+    (any::<bool>(), any::<bool>()).prop_map(|(hoist_entities, todo_comments)| {
+        ConvertOptions::builder()
+            .hoist_entities(hoist_entities)
+            .todo_comments(todo_comments)
+            .build()
+    })
+}
+
+/// The `observeEvents` of each client available to a converted test, keyed by the client's id.
+fn observed_events(file: &Yaml, test: &Yaml) -> BTreeMap<String, BTreeSet<String>> {
+    let mut entities: Vec<&Yaml> = file["createEntities"]
+        .as_sequence()
+        .into_iter()
+        .flatten()
+        .collect();
+    for op in test["operations"].as_sequence().into_iter().flatten() {
+        if op["name"].as_str() == Some("createEntities") {
+            entities.extend(op["arguments"]["entities"].as_sequence().into_iter().flatten());
+        }
+    }
+    entities
+        .into_iter()
+        .filter_map(|entity| {
+            let client = &entity["client"];
+            let id = client["id"].as_str()?.to_string();
+            let events = client["observeEvents"]
+                .as_sequence()
+                .into_iter()
+                .flatten()
+                .filter_map(|event| event.as_str().map(String::from))
+                .collect();
+            Some((id, events))
+        })
+        .collect()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 64,
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn output_is_valid_yaml(input in test_file(), options in options()) {
+        let (output, _) = convert_str(&input, &options).unwrap();
+        prop_assert!(serde_yaml::from_str::<Yaml>(&output).is_ok(), "{}", output);
+    }
+
+    #[test]
+    fn every_alias_follows_its_anchor(input in test_file(), options in options()) {
+        let (output, _) = convert_str(&input, &options).unwrap();
+        let anchor = Regex::new(r"&(\w+)").unwrap();
+        let alias = Regex::new(r"(?:^|[\s\[{,])\*(\w+)").unwrap();
+        for captures in alias.captures_iter(&output) {
+            let position = captures.get(0).unwrap().start();
+            let name = &captures[1];
+            prop_assert!(
+                anchor
+                    .captures_iter(&output[..position])
+                    .any(|anchor| &anchor[1] == name),
+                "*{} has no preceding &{}:\n{}",
+                name,
+                name,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn every_expected_event_is_observed(input in test_file(), options in options()) {
+        let (output, _) = convert_str(&input, &options).unwrap();
+        let file: Yaml = serde_yaml::from_str(&output).unwrap();
+        for test in file["tests"].as_sequence().into_iter().flatten() {
+            let observed = observed_events(&file, test);
+            for expected in test["expectEvents"].as_sequence().into_iter().flatten() {
+                let client = expected["client"].as_str().unwrap();
+                let events = observed.get(client);
+                prop_assert!(events.is_some(), "no client {}:\n{}", client, output);
+                for event in expected["events"].as_sequence().into_iter().flatten() {
+                    for name in event.as_mapping().into_iter().flat_map(|event| event.iter().map(|(name, _)| name)) {
+                        let name = name.as_str().unwrap();
+                        prop_assert!(
+                            events.unwrap().contains(name),
+                            "{} doesn't observe {}:\n{}",
+                            client,
+                            name,
+                            output
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn conversion_is_deterministic(input in test_file(), options in options()) {
+        let (first, first_report) = convert_str(&input, &options).unwrap();
+        let (second, second_report) = convert_str(&input, &options).unwrap();
+        prop_assert_eq!(first, second);
+        prop_assert_eq!(first_report.warnings, second_report.warnings);
+    }
+}
@@ -0,0 +1,91 @@
+//! Tests of the rewrites of legacy expectations into unified format matchers.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, ConvertOptions};
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+fn converted(legacy: &str) -> Yaml {
+    let (output, _) = convert_str(legacy, &ConvertOptions::builder().build()).unwrap();
+    yaml(&output)
+}
+
+/// The converted test's operation with the given name.
+fn operation<'a>(test: &'a Yaml, name: &str) -> &'a Yaml {
+    test["operations"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .find(|op| op["name"].as_str() == Some(name))
+        .unwrap()
+}
+
+#[test]
+fn placeholders_in_results_are_data() {
+    let file = converted(
+        r#"
+data:
+  - { _id: 1, x: 42 }
+collection_name: coll
+database_name: db
+tests:
+  - description: find
+    operations:
+      - name: find
+        object: collection
+        arguments: { filter: { x: 42 } }
+        result:
+          - { _id: 1, x: 42, y: "42" }
+    expectations:
+      - command_started_event:
+          command: { find: coll, filter: { x: 42 } }
+          command_name: find
+      - command_started_event:
+          command: { getMore: 42, collection: coll }
+          command_name: getMore
+"#,
+    );
+    let test = &file["tests"][0];
+    assert_eq!(
+        operation(test, "find")["expectResult"],
+        yaml(r#"[{ _id: 1, x: 42, y: "42" }]"#)
+    );
+
+    let events = &test["expectEvents"][0]["events"];
+    assert_eq!(
+        events[1]["commandStartedEvent"]["command"]["getMore"],
+        yaml(r#"{ $$type: [int, long] }"#)
+    );
+}
+
+#[test]
+fn null_fields_of_commands_are_absent() {
+    let file = converted(
+        r#"
+data: []
+collection_name: coll
+database_name: db
+tests:
+  - description: find
+    operations:
+      - name: find
+        object: collection
+        arguments: { filter: { x: null } }
+    expectations:
+      - command_started_event:
+          command:
+            find: coll
+            filter: { x: null }
+            readConcern: { level: null }
+            maxTimeMS: null
+          command_name: find
+"#,
+    );
+    let event = &file["tests"][0]["expectEvents"][0]["events"][0];
+    let command = &event["commandStartedEvent"]["command"];
+    assert_eq!(command["maxTimeMS"], yaml("{ $$exists: false }"));
+    assert_eq!(command["readConcern"]["level"], yaml("{ $$exists: false }"));
+    assert_eq!(command["filter"], yaml("{ x: null }"));
+}
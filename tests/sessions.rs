@@ -0,0 +1,51 @@
+//! Tests that every session a converted test refers to has an entity.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, ConvertOptions};
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+#[test]
+fn sessions_only_used_in_callbacks_are_created() {
+    let legacy = r#"
+data: []
+collection_name: coll
+database_name: db
+tests:
+  - description: callback session
+    operations:
+      - name: withTransaction
+        object: session0
+        arguments:
+          callback:
+            operations:
+              - name: withTransaction
+                object: session0
+                arguments:
+                  callback:
+                    operations:
+                      - name: insertOne
+                        object: collection
+                        arguments:
+                          session: session1
+                          document: { _id: 1 }
+"#;
+    let (output, _) = convert_str(legacy, &ConvertOptions::builder().build()).unwrap();
+    // an alias without an anchor would fail to parse.
+    let file = yaml(&output);
+    let test = &file["tests"][0];
+    let sessions: Vec<&Yaml> = test["operations"][0]["arguments"]["entities"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .filter_map(|entity| entity.get("session"))
+        .map(|session| &session["id"])
+        .collect();
+    assert_eq!(sessions, vec![&yaml("session0"), &yaml("session1")]);
+
+    let outer = &test["operations"][1]["arguments"]["callback"][0];
+    let insert = &outer["arguments"]["callback"][0];
+    assert_eq!(insert["arguments"]["session"], yaml("session1"));
+}
@@ -0,0 +1,79 @@
+//! Tests that URI options legacy files quote the way they'd appear in a connection string are
+//! converted to the types unified runners expect.
+
+use serde_yaml::Value as Yaml;
+use v2_to_unified::{convert_str, ConversionReport, ConvertOptions, WarningKind};
+
+fn yaml(s: &str) -> Yaml {
+    serde_yaml::from_str(s).unwrap()
+}
+
+/// Converts a legacy test whose client has the given `clientOptions`, returning the client's
+/// `uriOptions` along with the report.
+fn converted(client_options: &str) -> (Yaml, ConversionReport) {
+    let legacy = format!(
+        r#"
+data: []
+collection_name: coll
+database_name: db
+tests:
+  - description: client options
+    clientOptions: {}
+    operations:
+      - name: find
+        object: collection
+        arguments: {{ filter: {{}} }}
+"#,
+        client_options
+    );
+    let (output, report) = convert_str(&legacy, &ConvertOptions::builder().build()).unwrap();
+    let file = yaml(&output);
+    let client = &file["tests"][0]["operations"][0]["arguments"]["entities"][0]["client"];
+    (client["uriOptions"].clone(), report)
+}
+
+/// The coercions the report warns about, as the fields and the types they were converted to.
+fn coercions(report: &ConversionReport) -> Vec<(String, &'static str)> {
+    report
+        .warnings
+        .iter()
+        .filter_map(|warning| match warning.kind {
+            WarningKind::CoercedValue {
+                ref field,
+                expected,
+                ..
+            } => Some((field.clone(), expected)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn quoted_options_are_typed() {
+    let (uri_options, _) = converted(r#"{ retryWrites: "true", heartbeatFrequencyMS: "500" }"#);
+    assert_eq!(uri_options["retryWrites"], Yaml::Bool(true));
+    assert_eq!(uri_options["heartbeatFrequencyMS"], yaml("500"));
+}
+
+#[test]
+fn each_coercion_warns_once() {
+    let (_, report) = converted(r#"{ retryWrites: "true", heartbeatFrequencyMS: "500" }"#);
+    assert_eq!(
+        coercions(&report),
+        vec![
+            ("clientOptions.retryWrites".to_string(), "a boolean"),
+            (
+                "clientOptions.heartbeatFrequencyMS".to_string(),
+                "an integer"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn typed_options_are_unchanged() {
+    let (uri_options, report) = converted("{ retryWrites: false, heartbeatFrequencyMS: 500 }");
+    assert_eq!(uri_options["retryWrites"], Yaml::Bool(false));
+    assert_eq!(uri_options["heartbeatFrequencyMS"], yaml("500"));
+    assert!(coercions(&report).is_empty());
+}